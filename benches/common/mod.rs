@@ -0,0 +1,18 @@
+//! Shared by the benchmarks, which run without the test harness.
+
+use std::env;
+use std::process;
+
+use vulkano_fractals::DeviceChoice;
+
+/// The device from the command line, in the form `--device` takes, or the best one.
+pub fn device_choice() -> DeviceChoice {
+    /* Cargo passes `--bench` to benchmarks without the test harness */
+    let Some(arg) = env::args().skip(1).find(|arg| !arg.starts_with("--")) else {
+        return DeviceChoice::Best;
+    };
+    arg.parse().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(2);
+    })
+}
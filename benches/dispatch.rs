@@ -0,0 +1,89 @@
+//! CPU time per frame of recording and submitting a dispatch, with a new parameter buffer and
+//! descriptor set every frame ([`FractalRenderer::record_dispatch`]) and with the ones kept by a
+//! [`RenderTarget`] ([`FractalRenderer::record_target_dispatch`]).
+//!
+//! Waiting for the GPU is timed separately, the image is tiny and the iterations few so that
+//! should hardly differ. Run with `cargo bench --bench dispatch -- [device]`.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
+};
+use vulkano::sync::{self, GpuFuture};
+use vulkano_fractals::{FractalError, FractalKind, FractalRenderer};
+
+const EXTENT: [u32; 2] = [64, 64];
+const FRAMES: u32 = 1000;
+
+fn main() -> Result<(), FractalError> {
+    let renderer = FractalRenderer::new_headless(common::device_choice())?;
+
+    let kind = FractalKind::Mandelbrot;
+    let mut parameters = kind.default_parameters();
+    parameters.iterations = 10;
+
+    let image = renderer.create_image(EXTENT)?;
+    let allocating = time_frames(&renderer, |builder| {
+        renderer.record_dispatch(builder, image.clone(), kind, &parameters)
+    })?;
+
+    let mut target = renderer.create_target(EXTENT)?;
+    let persistent = time_frames(&renderer, |builder| {
+        renderer.record_target_dispatch(builder, &mut target, kind, &parameters)
+    })?;
+
+    println!("{:<32} {:>12} {:>12}", "", "us recording", "us waiting");
+    for (name, (recording, waiting)) in [
+        ("new buffer and set every frame", allocating),
+        ("render target", persistent),
+    ] {
+        println!(
+            "{name:<32} {:>12.1} {:>12.1}",
+            micros(recording),
+            micros(waiting)
+        );
+    }
+    Ok(())
+}
+
+/// Average time per frame to record and submit a command buffer filled by `record`, and to then
+/// wait for the GPU to finish it.
+///
+/// Every frame is waited for before the next, like a render target's slot would be reused.
+fn time_frames(
+    renderer: &FractalRenderer,
+    mut record: impl FnMut(
+        &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), FractalError>,
+) -> Result<(Duration, Duration), FractalError> {
+    let mut recording = Duration::ZERO;
+    let mut waiting = Duration::ZERO;
+
+    for _ in 0..FRAMES {
+        let start = Instant::now();
+        let mut builder = AutoCommandBufferBuilder::primary(
+            renderer.command_buffer_allocator(),
+            renderer.queue().queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+        record(&mut builder)?;
+
+        let future = sync::now(renderer.device().clone())
+            .then_execute(renderer.queue().clone(), builder.build()?)?
+            .then_signal_fence_and_flush()?;
+        recording += start.elapsed();
+
+        let start = Instant::now();
+        future.wait(None)?;
+        waiting += start.elapsed();
+    }
+
+    Ok((recording / FRAMES, waiting / FRAMES))
+}
+
+fn micros(time: Duration) -> f64 {
+    time.as_secs_f64() * 1_000_000.0
+}
@@ -0,0 +1,100 @@
+//! Renders the same views with every precision the device supports and compares their speed and
+//! output with doubles.
+//!
+//! Run with `cargo bench --bench precision -- [device]`, where `device` is a `--device` value
+//! (see `--list-devices`).
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use vulkano_fractals::{FractalError, FractalKind, FractalRenderer, Precision, View};
+
+const EXTENT: [u32; 2] = [1024, 1024];
+const RUNS: u32 = 5;
+
+fn main() -> Result<(), FractalError> {
+    let mut renderer = FractalRenderer::new_headless(common::device_choice())?;
+
+    let views = [
+        ("overview", FractalKind::Mandelbrot.default_view(), 300),
+        (
+            "seahorse valley 1e-5",
+            View {
+                center: [-0.743643887037151, 0.131825904205330],
+                scale: 1e-5,
+                rotation: 0.0,
+            },
+            1000,
+        ),
+        (
+            "seahorse valley 1e-11",
+            View {
+                center: [-0.743643887037151, 0.131825904205330],
+                scale: 1e-11,
+                rotation: 0.0,
+            },
+            3000,
+        ),
+    ];
+
+    println!(
+        "{:<24} {:<9} {:>10} {:>14} {:>16}",
+        "view", "precision", "ms/frame", "vs float64", "pixels differ"
+    );
+
+    for (name, view, iterations) in views {
+        let mut parameters = FractalKind::Mandelbrot.default_parameters();
+        parameters.iterations = iterations;
+        view.apply(&mut parameters);
+
+        let mut reference: Option<(Duration, Vec<u8>)> = None;
+
+        for precision in Precision::ALL {
+            if renderer.set_precision(precision).is_err() {
+                println!("{name:<24} {precision:<9} unsupported");
+                continue;
+            }
+
+            /* The first render also warms up the pipeline and allocators */
+            let pixels = renderer.render_to_rgba8(FractalKind::Mandelbrot, &parameters, EXTENT)?;
+
+            let start = Instant::now();
+            for _ in 0..RUNS {
+                renderer.render(FractalKind::Mandelbrot, &parameters, EXTENT)?;
+            }
+            let time = start.elapsed() / RUNS;
+
+            let (speedup, differ) = match &reference {
+                Some((reference_time, reference_pixels)) => (
+                    format!("{:.1}x", reference_time.as_secs_f64() / time.as_secs_f64()),
+                    format!(
+                        "{:.2}%",
+                        differing_pixels(reference_pixels, &pixels) * 100.0
+                    ),
+                ),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            println!(
+                "{name:<24} {precision:<9} {:>10.2} {speedup:>14} {differ:>16}",
+                time.as_secs_f64() * 1000.0
+            );
+
+            if precision == Precision::Float64 {
+                reference = Some((time, pixels));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Fraction of RGBA8 pixels that are more than one step apart in any channel.
+fn differing_pixels(a: &[u8], b: &[u8]) -> f64 {
+    let differing = a
+        .chunks_exact(4)
+        .zip(b.chunks_exact(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > 1))
+        .count();
+    differing as f64 / (a.len() / 4) as f64
+}
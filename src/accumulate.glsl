@@ -0,0 +1,30 @@
+// Progressive supersampling for the Julia and Mandelbrot shaders: instead of the whole grid of
+// `supersample` in one dispatch, every dispatch takes one sample of it and adds it to what the
+// previous ones left in `accumulation`. Summing in floats rather than in `img` keeps 8 bit
+// rounding from piling up over the samples.
+
+layout(set = 0, binding = 3, rgba32f) uniform image2D accumulation;
+
+// Sample `index` of the n x n grid of `supersample`, averaged with the ones before it. Sample 0
+// starts the sum over.
+vec4 accumulate_sample(int n, int index) {
+    n = max(n, 1);
+
+    const rvec2 subpixel = (rvec2(index % n, index / n) + rvec2(0.5)) / real(n);
+    const ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+    vec4 sum = sample_color(square_coordinates(subpixel));
+    if (index > 0) {
+        sum += imageLoad(accumulation, pixel);
+    }
+
+    imageStore(accumulation, pixel, sum);
+    return sum / float(index + 1);
+}
+
+// The whole grid, or just sample `p.sample_index` of it when that isn't negative
+vec4 pixel_color() {
+    if (p.sample_index < 0) {
+        return supersample(p.samples);
+    }
+    return accumulate_sample(p.samples, p.sample_index);
+}
@@ -0,0 +1,411 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::cs;
+use crate::error::FractalError;
+use crate::fractal::FractalKind;
+use crate::poster::render_poster;
+use crate::renderer::FractalRenderer;
+use crate::view::View;
+
+/// How the segment leading up to a keyframe speeds up and slows down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps the linear progress `t` in `[0, 1]` through the easing curve.
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Everything that can be animated, at `time` seconds into the animation.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct Keyframe {
+    pub time: f64,
+    pub center: [f64; 2],
+    pub scale: f64,
+    #[serde(default)]
+    pub rotation: f64,
+    /// Only used by Julia sets. When left out it stays what the previous keyframe has, or what
+    /// the next one has before the first keyframe with one. Without any, the constant isn't
+    /// animated.
+    #[serde(default)]
+    pub julia_c: Option<[f64; 2]>,
+    pub iterations: i32,
+    #[serde(default)]
+    pub palette_offset: f32,
+    /// Easing of the segment from the previous keyframe to this one.
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+impl Keyframe {
+    pub fn view(&self) -> View {
+        View {
+            center: self.center,
+            scale: self.scale,
+            rotation: self.rotation,
+        }
+    }
+
+    /// Writes the animated values into the shader parameters, leaving the rest untouched.
+    pub fn apply(&self, parameters: &mut cs::Parameters) {
+        self.view().apply(parameters);
+        if let Some(julia_c) = self.julia_c {
+            parameters.mouse_pos = julia_c;
+        }
+        parameters.iterations = self.iterations;
+        parameters.palette_offset = self.palette_offset;
+    }
+}
+
+/// Keyframes sorted by time, interpolated in between.
+///
+/// Loaded from JSON as `{"keyframes": [{"time": 0.0, "center": [-0.5, 0.0], "scale": 3.0,
+/// "iterations": 100}, ...]}`, see [`Keyframe`] for the optional fields. Deserializing goes
+/// through [`Animation::new`] too.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "AnimationFile")]
+pub struct Animation {
+    keyframes: Vec<Keyframe>,
+}
+
+/// What [`Animation`] deserializes from before it's checked.
+#[derive(Deserialize)]
+struct AnimationFile {
+    keyframes: Vec<Keyframe>,
+}
+
+impl TryFrom<AnimationFile> for Animation {
+    type Error = String;
+
+    fn try_from(file: AnimationFile) -> Result<Self, String> {
+        Animation::checked(file.keyframes)
+    }
+}
+
+impl Animation {
+    /// Sorts the keyframes by time and fills in left out Julia constants. Fails with
+    /// [`FractalError::InvalidAnimation`] without keyframes, with numbers that aren't finite, or
+    /// with a scale or iteration count that isn't positive.
+    pub fn new(keyframes: Vec<Keyframe>) -> Result<Self, FractalError> {
+        Animation::checked(keyframes).map_err(FractalError::InvalidAnimation)
+    }
+
+    /// See [`Animation::new`].
+    fn checked(mut keyframes: Vec<Keyframe>) -> Result<Self, String> {
+        if keyframes.is_empty() {
+            return Err("no keyframes".to_string());
+        }
+        for keyframe in &keyframes {
+            let time = keyframe.time;
+            let [x, y] = keyframe.center;
+            let [c_re, c_im] = keyframe.julia_c.unwrap_or_default();
+            let numbers = [
+                time,
+                x,
+                y,
+                keyframe.scale,
+                keyframe.rotation,
+                c_re,
+                c_im,
+                keyframe.palette_offset as f64,
+            ];
+            if !numbers.iter().all(|n| n.is_finite()) {
+                return Err(format!(
+                    "the keyframe at {time} s has a number that isn't finite"
+                ));
+            }
+            if keyframe.scale <= 0.0 {
+                return Err(format!(
+                    "the keyframe at {time} s has a scale that isn't positive"
+                ));
+            }
+            if keyframe.iterations <= 0 {
+                return Err(format!("the keyframe at {time} s has no iterations"));
+            }
+        }
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let mut julia_c = keyframes.iter().find_map(|k| k.julia_c);
+        for keyframe in &mut keyframes {
+            julia_c = keyframe.julia_c.or(julia_c);
+            keyframe.julia_c = julia_c;
+        }
+
+        Ok(Animation { keyframes })
+    }
+
+    /// Fails with [`FractalError::InvalidAnimation`] when it can't be read or has bad keyframes.
+    pub fn load(path: impl AsRef<Path>) -> Result<Animation, FractalError> {
+        let path = path.as_ref();
+        let error = |e: &dyn std::fmt::Display| {
+            FractalError::InvalidAnimation(format!("{}: {e}", path.display()))
+        };
+
+        let text = fs::read_to_string(path).map_err(|e| error(&e))?;
+        serde_json::from_str(&text).map_err(|e| error(&e))
+    }
+
+    /// Zooms from the whole set into `view` and back out again, over 40 seconds.
+    pub fn zoom_into(kind: FractalKind, view: View, julia_c: [f64; 2]) -> Self {
+        let overview = Keyframe {
+            time: 0.0,
+            center: kind.overview().center,
+            scale: kind.overview().scale,
+            rotation: view.rotation,
+            julia_c: Some(julia_c),
+            iterations: 100,
+            palette_offset: 0.0,
+            easing: Easing::EaseInOut,
+        };
+        let target = Keyframe {
+            time: 20.0,
+            center: view.center,
+            scale: view.scale,
+            iterations: 100 + (-view.scale.log10().min(0.0) * 100.0) as i32,
+            palette_offset: 0.5,
+            ..overview
+        };
+
+        Animation {
+            keyframes: vec![
+                overview,
+                target,
+                Keyframe {
+                    time: 40.0,
+                    palette_offset: 1.0,
+                    ..overview
+                },
+            ],
+        }
+    }
+
+    /// Time of the first keyframe, which needn't be zero.
+    pub fn start(&self) -> f64 {
+        self.keyframes[0].time
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.keyframes.last().unwrap().time - self.start()
+    }
+
+    /// Number of frames at `fps` from the first keyframe up to and including the last one.
+    pub fn frame_count(&self, fps: f64) -> u32 {
+        (self.duration() * fps).floor() as u32 + 1
+    }
+
+    /// The interpolated keyframe at `time`, holding the first and last keyframes outside of the
+    /// animation.
+    pub fn sample(&self, time: f64) -> Keyframe {
+        let next = self.keyframes.iter().position(|k| k.time > time);
+        let (from, to) = match next {
+            Some(0) => return self.keyframes[0],
+            None => return *self.keyframes.last().unwrap(),
+            Some(next) => (&self.keyframes[next - 1], &self.keyframes[next]),
+        };
+
+        let t = to.easing.apply((time - from.time) / (to.time - from.time));
+        let lerp = |a: f64, b: f64| a + (b - a) * t;
+
+        // Zooming at a constant rate needs the scale to change geometrically
+        let scale = from.scale * (to.scale / from.scale).powf(t);
+
+        // Move the center along with the zoom, so that when zooming in the target center stays
+        // put on screen instead of drifting in from the side
+        let zoom = from.scale - to.scale;
+        let progress = if zoom.abs() > f64::EPSILON * from.scale {
+            (from.scale - scale) / zoom
+        } else {
+            t
+        };
+
+        Keyframe {
+            time,
+            center: [
+                from.center[0] + (to.center[0] - from.center[0]) * progress,
+                from.center[1] + (to.center[1] - from.center[1]) * progress,
+            ],
+            scale,
+            rotation: lerp(from.rotation, to.rotation),
+            // Either both keyframes have one or neither, see `Animation::new`
+            julia_c: from
+                .julia_c
+                .zip(to.julia_c)
+                .map(|(a, b)| [lerp(a[0], b[0]), lerp(a[1], b[1])]),
+            iterations: lerp(from.iterations as f64, to.iterations as f64).round() as i32,
+            palette_offset: lerp(from.palette_offset as f64, to.palette_offset as f64) as f32,
+            easing: to.easing,
+        }
+    }
+}
+
+/// Renders `frames` frames of `animation` at a fixed `fps` into `frame_00000.png`,
+/// `frame_00001.png`, ... in `directory`, taking everything that isn't animated from
+/// `parameters`. [`Animation::frame_count`] is the whole animation.
+///
+/// Frame times only depend on the frame number, so the same animation always renders the same
+/// frames no matter how long each of them takes.
+#[allow(clippy::too_many_arguments)]
+pub fn render_frames(
+    renderer: &FractalRenderer,
+    kind: FractalKind,
+    parameters: &cs::Parameters,
+    animation: &Animation,
+    fps: f64,
+    frames: u32,
+    extent: [u32; 2],
+    directory: impl AsRef<Path>,
+) -> Result<(), FractalError> {
+    let directory = directory.as_ref();
+    fs::create_dir_all(directory)?;
+
+    let start = animation.start();
+
+    for frame in 0..frames {
+        let time = start + frame as f64 / fps;
+
+        let mut frame_parameters = *parameters;
+        animation.sample(time).apply(&mut frame_parameters);
+        frame_parameters.time = time;
+
+        let path = directory.join(format!("frame_{frame:05}.png"));
+        render_poster(renderer, kind, &frame_parameters, extent, path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f64, scale: f64) -> Keyframe {
+        Keyframe {
+            time,
+            center: [0.0, 0.0],
+            scale,
+            rotation: 0.0,
+            julia_c: None,
+            iterations: 100,
+            palette_offset: 0.0,
+            easing: Easing::Linear,
+        }
+    }
+
+    #[test]
+    fn easing_keeps_the_ends() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0, "{easing:?}");
+            assert_eq!(easing.apply(1.0), 1.0, "{easing:?}");
+        }
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn rejects_bad_keyframes() {
+        assert!(Animation::new(vec![]).is_err());
+        for scale in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(
+                Animation::new(vec![keyframe(0.0, scale)]).is_err(),
+                "{scale}"
+            );
+        }
+        let no_iterations = Keyframe {
+            iterations: 0,
+            ..keyframe(0.0, 1.0)
+        };
+        assert!(Animation::new(vec![no_iterations]).is_err());
+        let bad_center = Keyframe {
+            center: [f64::NAN, 0.0],
+            ..keyframe(0.0, 1.0)
+        };
+        assert!(Animation::new(vec![bad_center]).is_err());
+        assert!(Animation::new(vec![keyframe(f64::INFINITY, 1.0)]).is_err());
+    }
+
+    #[test]
+    fn deserializing_checks_and_sorts() {
+        assert!(serde_json::from_str::<Animation>(r#"{"keyframes": []}"#).is_err());
+        let negative = r#"{"keyframes": [{"time": 0, "center": [0, 0], "scale": -1,
+            "iterations": 100}]}"#;
+        assert!(serde_json::from_str::<Animation>(negative).is_err());
+
+        let unsorted = r#"{"keyframes": [
+            {"time": 2, "center": [0, 0], "scale": 1, "iterations": 100},
+            {"time": 1, "center": [0, 0], "scale": 2, "iterations": 100}]}"#;
+        let animation: Animation = serde_json::from_str(unsorted).unwrap();
+        assert_eq!(animation.start(), 1.0);
+        assert_eq!(animation.duration(), 1.0);
+    }
+
+    #[test]
+    fn sample_holds_the_ends_and_zooms_geometrically() {
+        let animation = Animation::new(vec![keyframe(1.0, 4.0), keyframe(3.0, 1.0)]).unwrap();
+        assert_eq!(animation.sample(0.0).scale, 4.0);
+        assert_eq!(animation.sample(1.0).scale, 4.0);
+        assert_eq!(animation.sample(5.0).scale, 1.0);
+        assert!((animation.sample(2.0).scale - 2.0).abs() < 1e-12);
+        assert_eq!(animation.frame_count(10.0), 21);
+    }
+
+    #[test]
+    fn sample_keeps_the_center_on_screen_while_zooming() {
+        let from = keyframe(0.0, 4.0);
+        let to = Keyframe {
+            center: [1.0, -1.0],
+            ..keyframe(1.0, 1.0)
+        };
+        let animation = Animation::new(vec![from, to]).unwrap();
+
+        // Halfway through the zoom the center has moved by as much as the scale shrank
+        let sample = animation.sample(0.5);
+        let progress = (4.0 - sample.scale) / 3.0;
+        assert!((sample.center[0] - progress).abs() < 1e-12);
+        assert!((sample.center[1] + progress).abs() < 1e-12);
+    }
+
+    #[test]
+    fn julia_constants_carry_over() {
+        let with = |time, julia_c| Keyframe {
+            julia_c,
+            ..keyframe(time, 1.0)
+        };
+        let animation = Animation::new(vec![
+            with(0.0, None),
+            with(1.0, Some([0.5, 0.5])),
+            with(2.0, None),
+            with(3.0, Some([-0.5, 0.0])),
+        ])
+        .unwrap();
+
+        assert_eq!(animation.sample(0.5).julia_c, Some([0.5, 0.5]));
+        assert_eq!(animation.sample(1.5).julia_c, Some([0.5, 0.5]));
+        assert_eq!(animation.sample(2.5).julia_c, Some([0.0, 0.25]));
+
+        let without = Animation::new(vec![keyframe(0.0, 1.0), keyframe(1.0, 1.0)]).unwrap();
+        assert_eq!(without.sample(0.5).julia_c, None);
+    }
+}
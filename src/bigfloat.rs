@@ -0,0 +1,352 @@
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+use std::str::FromStr;
+
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+/// An arbitrary precision fixed point number, `value / 2^bits`.
+///
+/// Only what deep zooming needs: enough precision to place the view center and iterate a
+/// reference orbit past the ~1e-16 relative precision of `f64`. Mixing numbers with different
+/// `bits` keeps the larger precision.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigFixed {
+    value: BigInt,
+    bits: u32,
+}
+
+impl BigFixed {
+    pub fn zero(bits: u32) -> Self {
+        BigFixed {
+            value: BigInt::zero(),
+            bits,
+        }
+    }
+
+    /// Converts exactly, every finite `f64` is representable with enough `bits`. `None` for NaN
+    /// and infinities.
+    pub fn from_f64(x: f64, bits: u32) -> Option<Self> {
+        if !x.is_finite() {
+            return None;
+        }
+
+        // Split into an integer mantissa and a power of two, then line up the exponent.
+        let (mantissa, exponent) = frexp(x);
+        let mantissa = BigInt::from((mantissa * (1u64 << 53) as f64) as i64);
+        let shift = exponent as i64 - 53 + bits as i64;
+
+        let value = if shift >= 0 {
+            mantissa << shift as u64
+        } else {
+            mantissa >> (-shift) as u64
+        };
+
+        Some(BigFixed { value, bits })
+    }
+
+    /// Closest `f64`, accurate to the `f64` mantissa.
+    pub fn to_f64(&self) -> f64 {
+        // Keep the top 64 bits of the value and account for the rest in the exponent.
+        let shift = self.value.bits().saturating_sub(63);
+        let top = (&self.value >> shift).to_i64().unwrap();
+        ldexp(top as f64, shift as i32 - self.bits as i32)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Changes the precision, dropping the lowest bits when it gets smaller.
+    pub fn with_bits(&self, bits: u32) -> Self {
+        let value = if bits >= self.bits {
+            &self.value << (bits - self.bits)
+        } else {
+            &self.value >> (self.bits - bits)
+        };
+        BigFixed { value, bits }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.value.is_negative()
+    }
+
+    /// Brings both numbers to the larger of the two precisions.
+    fn aligned(&self, other: &BigFixed) -> (BigInt, BigInt, u32) {
+        let bits = self.bits.max(other.bits);
+        (
+            self.with_bits(bits).value,
+            other.with_bits(bits).value,
+            bits,
+        )
+    }
+}
+
+impl Add for &BigFixed {
+    type Output = BigFixed;
+
+    fn add(self, other: &BigFixed) -> BigFixed {
+        let (a, b, bits) = self.aligned(other);
+        BigFixed { value: a + b, bits }
+    }
+}
+
+impl Sub for &BigFixed {
+    type Output = BigFixed;
+
+    fn sub(self, other: &BigFixed) -> BigFixed {
+        let (a, b, bits) = self.aligned(other);
+        BigFixed { value: a - b, bits }
+    }
+}
+
+impl Mul for &BigFixed {
+    type Output = BigFixed;
+
+    fn mul(self, other: &BigFixed) -> BigFixed {
+        let (a, b, bits) = self.aligned(other);
+        BigFixed {
+            value: (a * b) >> bits,
+            bits,
+        }
+    }
+}
+
+/// Largest decimal exponent `FromStr` accepts either way. Smaller numbers are zero even at the
+/// precision of the deepest zoom, and larger ones are far outside any fractal.
+const MAX_EXPONENT: i64 = 1000;
+
+impl FromStr for BigFixed {
+    type Err = String;
+
+    /// Parses a decimal number like `-0.7436438870371587047521915061` or `1.5e-3`, with enough
+    /// precision for all of its digits.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("invalid number `{s}`");
+
+        let (number, exponent) = match s.find(['e', 'E']) {
+            Some(index) => {
+                let exponent: i64 = s[index + 1..].parse().map_err(|_| error())?;
+                if !(-MAX_EXPONENT..=MAX_EXPONENT).contains(&exponent) {
+                    return Err(format!("exponent of `{s}` is out of range"));
+                }
+                (&s[..index], exponent)
+            }
+            None => (s, 0),
+        };
+
+        let (negative, number) = match number.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, number.strip_prefix('+').unwrap_or(number)),
+        };
+
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let digits = format!("{integer}{fraction}");
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(error());
+        }
+
+        // value = digits * 10^exponent
+        let exponent = exponent - fraction.len() as i64;
+        let mut mantissa: BigInt = digits.parse().map_err(|_| error())?;
+        if negative {
+            mantissa = -mantissa;
+        }
+
+        // log2(10) ~ 3.33 bits per decimal digit, plus some headroom
+        let bits = (digits.len() as i64 * 10 / 3)
+            .max(-exponent * 10 / 3)
+            .max(0);
+        let bits = u32::try_from(64 + bits).map_err(|_| error())?;
+
+        let value = if exponent >= 0 {
+            (mantissa * BigInt::from(10).pow(exponent as u32)) << bits
+        } else {
+            (mantissa << bits) / BigInt::from(10).pow((-exponent) as u32)
+        };
+
+        Ok(BigFixed { value, bits })
+    }
+}
+
+impl fmt::Display for BigFixed {
+    /// Writes every significant decimal digit, which round trips through `FromStr`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_negative() {
+            f.write_str("-")?;
+        }
+
+        let magnitude = self.value.abs();
+        let integer = &magnitude >> self.bits;
+        let mut fraction = magnitude - (&integer << self.bits);
+        write!(f, "{integer}")?;
+
+        // Roughly 3.3 bits per decimal digit, digits past that are noise.
+        let mut digits = String::new();
+        let ten = BigInt::from(10);
+        for _ in 0..(self.bits as usize * 3 / 10 + 1) {
+            fraction *= &ten;
+            let digit = &fraction >> self.bits;
+            fraction -= &digit << self.bits;
+            digits.push_str(&digit.to_string());
+            if fraction.is_zero() {
+                break;
+            }
+        }
+
+        let digits = digits.trim_end_matches('0');
+        if digits.is_empty() {
+            return Ok(());
+        }
+        write!(f, ".{digits}")
+    }
+}
+
+/// Splits `x` into a mantissa in `[0.5, 1)` (with the sign of `x`) and a power of two.
+fn frexp(x: f64) -> (f64, i32) {
+    if x == 0.0 {
+        return (0.0, 0);
+    }
+
+    let mut exponent = x.abs().log2().floor() as i32 + 1;
+    let mut mantissa = ldexp(x, -exponent);
+    // log2 can be off by one near powers of two
+    if mantissa.abs() >= 1.0 {
+        mantissa /= 2.0;
+        exponent += 1;
+    } else if mantissa.abs() < 0.5 {
+        mantissa *= 2.0;
+        exponent -= 1;
+    }
+    (mantissa, exponent)
+}
+
+/// `x * 2^exponent` without overflowing the intermediate power of two.
+fn ldexp(mut x: f64, mut exponent: i32) -> f64 {
+    while exponent > 1000 {
+        x *= 2f64.powi(1000);
+        exponent -= 1000;
+    }
+    while exponent < -1000 {
+        x *= 2f64.powi(-1000);
+        exponent += 1000;
+    }
+    x * 2f64.powi(exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> BigFixed {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn f64_round_trip() {
+        for x in [
+            0.0,
+            1.0,
+            -2.5,
+            0.1,
+            -0.7436438870371587,
+            1e10,
+            3e-300,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+        ] {
+            let bits = 1152;
+            assert_eq!(BigFixed::from_f64(x, bits).unwrap().to_f64(), x);
+        }
+    }
+
+    #[test]
+    fn from_f64_rejects_non_finite() {
+        assert_eq!(BigFixed::from_f64(f64::NAN, 64), None);
+        assert_eq!(BigFixed::from_f64(f64::INFINITY, 64), None);
+        assert_eq!(BigFixed::from_f64(f64::NEG_INFINITY, 64), None);
+    }
+
+    #[test]
+    fn from_f64_drops_bits_below_precision() {
+        assert_eq!(BigFixed::from_f64(1.75, 1).unwrap().to_f64(), 1.5);
+        assert_eq!(BigFixed::from_f64(-0.25, 1).unwrap().to_f64(), -0.5);
+    }
+
+    #[test]
+    fn parses_decimals() {
+        assert_eq!(parse("1.5e-3").to_f64(), 1.5e-3);
+        assert_eq!(parse("-0.75").to_f64(), -0.75);
+        assert_eq!(parse("+2").to_f64(), 2.0);
+        assert_eq!(parse("12.5E2").to_f64(), 1250.0);
+        assert_eq!(parse(".5").to_f64(), 0.5);
+    }
+
+    #[test]
+    fn keeps_digits_past_f64() {
+        let a = parse("0.10000000000000000000000000000001");
+        let b = parse("0.10000000000000000000000000000000");
+        let difference = &a - &b;
+        assert!((difference.to_f64() - 1e-32).abs() < 1e-40);
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        for s in [
+            "", "-", ".", "abc", "1.2.3", "1e", "1e1.5", "--1", "NaN", "inf", "1 ",
+        ] {
+            assert!(s.parse::<BigFixed>().is_err(), "`{s}` parsed");
+        }
+    }
+
+    #[test]
+    fn rejects_exponents_out_of_range() {
+        for s in [
+            "1e1001",
+            "1e-1001",
+            "1e99999999999",
+            "1e-4294967297",
+            "1e-9223372036854775808",
+        ] {
+            assert!(s.parse::<BigFixed>().is_err(), "`{s}` parsed");
+        }
+        assert!("1e-1000".parse::<BigFixed>().is_ok());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for s in [
+            "0",
+            "-1.25",
+            "0.7436438870371587047521915061",
+            "-1234.000001",
+        ] {
+            let x = parse(s);
+            assert_eq!(
+                x.to_string().parse::<BigFixed>().unwrap().to_f64(),
+                x.to_f64()
+            );
+        }
+        assert_eq!(parse("-1.25").to_string(), "-1.25");
+        assert_eq!(parse("3").to_string(), "3");
+    }
+
+    #[test]
+    fn arithmetic_keeps_larger_precision() {
+        let a = BigFixed::from_f64(1.5, 8).unwrap();
+        let b = BigFixed::from_f64(0.25, 64).unwrap();
+        assert_eq!((&a + &b).bits(), 64);
+        assert_eq!((&a + &b).to_f64(), 1.75);
+        assert_eq!((&a - &b).to_f64(), 1.25);
+        assert_eq!((&a * &b).to_f64(), 0.375);
+        assert_eq!((&b - &a).to_f64(), -1.25);
+        assert!((&b - &a).is_negative());
+    }
+
+    #[test]
+    fn with_bits_grows_and_truncates() {
+        let x = BigFixed::from_f64(0.625, 16).unwrap();
+        assert_eq!(x.with_bits(64).to_f64(), 0.625);
+        assert_eq!(x.with_bits(2).to_f64(), 0.5);
+    }
+}
@@ -0,0 +1,375 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bigfloat::BigFixed;
+use crate::coloring::{Coloring, DEFAULT_BAILOUT};
+use crate::cs;
+use crate::deep::DeepView;
+use crate::error::FractalError;
+use crate::fractal::FractalKind;
+use crate::palette::Palette;
+use crate::view::View;
+
+/// Keyword of the PNG iTXt chunk that rendered images store their [`Bookmark`] in. It's UTF-8,
+/// unlike tEXt, because palette file names can be anything.
+pub const PNG_KEYWORD: &str = "Fractal";
+
+/// A location worth coming back to, saved as JSON:
+///
+/// ```json
+/// {
+///   "kind": "mandelbrot",
+///   "center": ["-0.7451544", "0.1853"],
+///   "scale": 3.0,
+///   "julia_c": [0.0, 0.0],
+///   "iterations": 300,
+///   "coloring": "smooth",
+///   "palette": "rainbow"
+/// }
+/// ```
+///
+/// Rendered PNGs carry the same JSON in an iTXt chunk (see [`PNG_KEYWORD`]), and load like
+/// bookmark files.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub kind: FractalKind,
+    /// Decimal strings, so deep zoom centers keep all of their digits.
+    pub center: [String; 2],
+    pub scale: f64,
+    #[serde(default)]
+    pub rotation: f64,
+    /// Whether the view needs deep zoom, see [`DeepView`].
+    #[serde(default)]
+    pub deep: bool,
+    pub julia_c: [f64; 2],
+    pub iterations: i32,
+    pub coloring: Coloring,
+    #[serde(default = "default_bailout")]
+    pub bailout: f64,
+    /// A built-in palette name or a gradient file, see [`Palette::from_name_or_path`].
+    pub palette: String,
+    #[serde(default)]
+    pub palette_offset: f32,
+}
+
+fn default_bailout() -> f64 {
+    DEFAULT_BAILOUT
+}
+
+impl Bookmark {
+    /// Describes what is on screen. `view` is an offset from `deep_view` when there is one, like
+    /// in interactive deep zoom. A palette loaded from a file is stored with its absolute path,
+    /// so the bookmark still finds it when opened from another directory.
+    pub fn new(
+        kind: FractalKind,
+        view: &View,
+        deep_view: Option<&DeepView>,
+        parameters: &cs::Parameters,
+        palette: &Palette,
+    ) -> Self {
+        let (center, deep) = match deep_view {
+            Some(deep_view) => {
+                // Fold the offset in without touching the caller's views
+                let mut deep_view = deep_view.clone();
+                deep_view.recenter(&mut view.clone());
+                (deep_view.center.map(|c| c.to_string()), true)
+            }
+            None => (view.center.map(|c| c.to_string()), false),
+        };
+
+        Bookmark {
+            kind,
+            center,
+            scale: view.scale,
+            rotation: view.rotation,
+            deep,
+            julia_c: parameters.mouse_pos,
+            iterations: parameters.iterations,
+            coloring: Coloring::from_i32(parameters.coloring).unwrap_or(Coloring::Smooth),
+            bailout: parameters.bailout,
+            palette: palette_path(palette),
+            palette_offset: parameters.palette_offset,
+        }
+    }
+
+    /// Loads a bookmark file, or the bookmark embedded in a PNG rendered by [`render_poster`].
+    /// Fails with [`FractalError::InvalidBookmark`] when it can't be read, or like the command
+    /// line would on numbers that are out of range.
+    ///
+    /// [`render_poster`]: crate::render_poster
+    pub fn load(path: impl AsRef<Path>) -> Result<Bookmark, FractalError> {
+        let path = path.as_ref();
+        let error = |e: &dyn std::fmt::Display| {
+            FractalError::InvalidBookmark(format!("{}: {e}", path.display()))
+        };
+
+        let is_png = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"));
+        let text = if is_png {
+            read_png_text(path).map_err(|e| error(&e))?
+        } else {
+            fs::read_to_string(path).map_err(|e| error(&e))?
+        };
+        let bookmark: Bookmark = serde_json::from_str(&text).map_err(|e| error(&e))?;
+
+        bookmark.check().map_err(|e| error(&e))?;
+        Ok(bookmark)
+    }
+
+    /// Catches what would otherwise only go wrong once rendering, like NaN colors from a bailout
+    /// that isn't larger than 1.
+    fn check(&self) -> Result<(), String> {
+        self.rounded_view()?;
+        if !(self.scale > 0.0 && self.scale.is_finite()) {
+            return Err(format!(
+                "the scale needs to be positive, got {}",
+                self.scale
+            ));
+        }
+        let numbers = [self.rotation, self.julia_c[0], self.julia_c[1]];
+        if !numbers.iter().all(|n| n.is_finite()) || !self.palette_offset.is_finite() {
+            return Err("the rotation, Julia constant and palette offset need to be finite".into());
+        }
+        if self.iterations <= 0 {
+            return Err(format!(
+                "there needs to be at least one iteration, got {}",
+                self.iterations
+            ));
+        }
+        if self.bailout.is_nan() || self.bailout <= 1.0 {
+            return Err(format!(
+                "the bailout needs to be larger than 1, got {}",
+                self.bailout
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FractalError> {
+        Ok(fs::write(path, self.to_json())?)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// The view with all the precision of the saved center. Fails with
+    /// [`FractalError::InvalidBookmark`] if the center isn't a number.
+    pub fn deep_view(&self) -> Result<DeepView, FractalError> {
+        self.parse_deep_view()
+            .map_err(FractalError::InvalidBookmark)
+    }
+
+    /// The view with its center rounded to `f64`. Fails with [`FractalError::InvalidBookmark`]
+    /// if the center isn't a number, or too large for an `f64`.
+    pub fn view(&self) -> Result<View, FractalError> {
+        self.rounded_view().map_err(FractalError::InvalidBookmark)
+    }
+
+    fn parse_deep_view(&self) -> Result<DeepView, String> {
+        Ok(DeepView {
+            center: [
+                self.center[0].parse::<BigFixed>()?,
+                self.center[1].parse::<BigFixed>()?,
+            ],
+            scale: self.scale,
+            rotation: self.rotation,
+        })
+    }
+
+    fn rounded_view(&self) -> Result<View, String> {
+        let view = self.parse_deep_view()?.to_view();
+        if !view.center.iter().all(|c| c.is_finite()) {
+            return Err(format!(
+                "the center {}, {} is too far out",
+                self.center[0], self.center[1]
+            ));
+        }
+        Ok(view)
+    }
+
+    /// Shader parameters for the whole bookmark, with the view center rounded to `f64`. Fails
+    /// like [`Bookmark::view`].
+    pub fn parameters(&self) -> Result<cs::Parameters, FractalError> {
+        let mut parameters = cs::Parameters {
+            mouse_pos: self.julia_c,
+            iterations: self.iterations,
+            coloring: self.coloring as i32,
+            bailout: self.bailout,
+            palette_offset: self.palette_offset,
+            ..self.kind.default_parameters()
+        };
+        self.view()?.apply(&mut parameters);
+        Ok(parameters)
+    }
+
+    pub fn palette(&self) -> Result<Palette, FractalError> {
+        Palette::from_name_or_path(&self.palette)
+    }
+}
+
+/// The name of a built-in palette, otherwise the canonical path of the file it was loaded from.
+/// A file that is gone by now keeps the path it was loaded with.
+fn palette_path(palette: &Palette) -> String {
+    if Palette::built_in(&palette.name).is_some() {
+        return palette.name.clone();
+    }
+    fs::canonicalize(&palette.name)
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| palette.name.clone())
+}
+
+/// The text of the [`PNG_KEYWORD`] chunk, which comes before the image data.
+fn read_png_text(path: &Path) -> Result<String, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let reader = png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .map_err(|e| e.to_string())?;
+
+    // Images from before the bookmark moved to iTXt have it in a tEXt chunk
+    let info = reader.info();
+    if let Some(chunk) = info
+        .utf8_text
+        .iter()
+        .find(|chunk| chunk.keyword == PNG_KEYWORD)
+    {
+        return chunk.get_text().map_err(|e| e.to_string());
+    }
+    info.uncompressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == PNG_KEYWORD)
+        .map(|chunk| chunk.text.clone())
+        .ok_or_else(|| "the image has no fractal metadata".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1x1 PNG at `name` in the temporary directory, with `chunk` added to its encoder.
+    fn write_png(name: &str, chunk: impl FnOnce(&mut png::Encoder<File>)) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let mut encoder = png::Encoder::new(File::create(&path).unwrap(), 1, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        chunk(&mut encoder);
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&[0; 4])
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_utf8_text() {
+        let text = r#"{"palette": "paletten/blå ✓.json"}"#;
+        let path = write_png("vulkano-fractals-itxt.png", |encoder| {
+            encoder
+                .add_itxt_chunk(PNG_KEYWORD.to_string(), text.to_string())
+                .unwrap();
+        });
+        assert_eq!(read_png_text(&path).unwrap(), text);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reads_latin1_text() {
+        let text = r#"{"palette": "fire"}"#;
+        let path = write_png("vulkano-fractals-text.png", |encoder| {
+            encoder
+                .add_text_chunk(PNG_KEYWORD.to_string(), text.to_string())
+                .unwrap();
+        });
+        assert_eq!(read_png_text(&path).unwrap(), text);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn stores_absolute_palette_paths() {
+        let path = std::env::temp_dir().join("vulkano-fractals-palette.txt");
+        fs::write(&path, "#000000\n#ffffff\n").unwrap();
+
+        let relative = relative_to_current_dir(&path);
+        let palette = Palette::load(&relative).unwrap();
+        let stored = palette_path(&palette);
+        fs::remove_file(&path).unwrap();
+
+        assert!(Path::new(&stored).is_absolute(), "{stored}");
+        assert_eq!(palette_path(&Palette::built_in("fire").unwrap()), "fire");
+    }
+
+    fn bookmark() -> Bookmark {
+        let parameters = FractalKind::Mandelbrot.default_parameters();
+        let view = View::from(&parameters);
+        let palette = Palette::built_in("fire").unwrap();
+        Bookmark::new(FractalKind::Mandelbrot, &view, None, &parameters, &palette)
+    }
+
+    #[test]
+    fn view_fails_on_bad_centers() {
+        assert!(bookmark().view().is_ok());
+        for center in ["north", "1e400"] {
+            let bad = Bookmark {
+                center: [center.to_string(), "0".to_string()],
+                ..bookmark()
+            };
+            assert!(bad.view().is_err(), "{center}");
+            assert!(bad.parameters().is_err(), "{center}");
+        }
+    }
+
+    #[test]
+    fn load_checks_numbers() {
+        let path = std::env::temp_dir().join("vulkano-fractals-bookmark.json");
+        let load = |bookmark: Bookmark| {
+            bookmark.save(&path).unwrap();
+            Bookmark::load(&path)
+        };
+
+        assert_eq!(load(bookmark()).unwrap(), bookmark());
+        let bad = [
+            Bookmark {
+                bailout: 1.0,
+                ..bookmark()
+            },
+            Bookmark {
+                iterations: 0,
+                ..bookmark()
+            },
+            Bookmark {
+                scale: 0.0,
+                ..bookmark()
+            },
+            Bookmark {
+                center: ["1e400".to_string(), "0".to_string()],
+                ..bookmark()
+            },
+        ];
+        for bookmark in bad {
+            assert!(load(bookmark.clone()).is_err(), "{bookmark:?}");
+        }
+
+        // JSON has no NaN, but a large enough number still turns into infinity
+        let text = bookmark()
+            .to_json()
+            .replace("\"scale\": 3.0", "\"scale\": 1e400");
+        assert!(text.contains("1e400"));
+        fs::write(&path, text).unwrap();
+        assert!(Bookmark::load(&path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    /// `path` relative to the current directory, through as many `..` as it takes.
+    fn relative_to_current_dir(path: &Path) -> std::path::PathBuf {
+        let current = std::env::current_dir().unwrap();
+        let mut relative = std::path::PathBuf::new();
+        for _ in current.components().skip(1) {
+            relative.push("..");
+        }
+        relative.join(path.strip_prefix("/").unwrap())
+    }
+}
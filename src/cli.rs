@@ -0,0 +1,478 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use vulkano_fractals::{
+    cs, Animation, BigFixed, Bookmark, Coloring, DeepView, DeviceChoice, FractalKind, Palette,
+    Precision, View,
+};
+
+/// Supersampling takes `samples`² samples per pixel, which is already 256 at this.
+pub const MAX_SAMPLES: i32 = 16;
+
+pub const USAGE: &str = "\
+Usage: vulkano-fractals [options]
+
+Opens a window by default, see the key bindings printed at startup.
+
+Modes:
+  --interactive               open a window (the default)
+  --headless [output.png]     render one image without a window and exit
+  --output <path>             render one image to <path> without a window and exit
+  --frames <directory>        render the animation into numbered PNGs and exit
+  --size <width>x<height>     size of rendered images and frames, 1024x1024 by default
+  --device <index|name|cpu>   use this physical device instead of the best one, by index or
+                              name from --list-devices, or cpu for a software renderer
+  --list-devices              show the physical devices and exit
+  --frames-in-flight <n>      frames the window renders ahead of the display, 2 by default
+  --precision float64|df64|float32
+                              arithmetic of the shaders, float64 by default, or df64 on
+                              devices without shader_float64
+
+View:
+  --bookmark <file.json|image.png>
+                              start from a saved view or a rendered image
+  --kind julia|mandelbrot     fractal to show, julia by default
+  --center <re>,<im>          center of the view, with as many digits as --deep needs
+  --scale <span>              span of the shorter side of the image
+  --rotation <degrees>        counterclockwise rotation of the view
+  --deep                      start in perturbation deep zoom, only in the window
+  --julia-c <re>,<im>         constant of the Julia set
+  --iterations <n>            maximum number of iterations
+
+Colors:
+  --coloring smooth|banded    how escaped points are colored
+  --bailout <radius>          escape radius
+  --palette <name|file>       built-in palette (rainbow, classic, fire, ocean, grayscale) or a
+                              gradient file
+  --palette-offset <x>        shift along the palette
+  --palette-speed <x>         palette cycling speed, in palettes per second
+  --samples <n>               supersample every pixel with an n x n grid, n up to 16
+
+Animation:
+  --animation <keyframes.json>
+                              keyframes played with Space, or rendered with --frames
+  --fps <n>                   frames per second for --frames, 30 by default
+  --frame-count <n>           number of frames for --frames, the whole animation by default
+
+  --help                      show this message
+
+Exit status: 0 on success, 1 when rendering fails, 2 for bad arguments, 3 when there is no
+usable Vulkan device.
+";
+
+/// What to do once the options are parsed.
+pub enum Mode {
+    Interactive,
+    Headless { output: String },
+    Frames { directory: String },
+    ListDevices,
+}
+
+/// Everything from the command line, with bookmarks and defaults already resolved.
+pub struct Options {
+    pub mode: Mode,
+    pub extent: [u32; 2],
+    pub device: DeviceChoice,
+    /// `None` keeps the device's default, see `Precision::for_device`.
+    pub precision: Option<Precision>,
+    /// Each one has its own images, so the GPU can render a frame while showing the last one.
+    pub frames_in_flight: usize,
+    pub kind: FractalKind,
+    /// The view is applied, `mouse_pos` is the Julia constant even when showing the Mandelbrot set.
+    pub parameters: cs::Parameters,
+    /// Set when starting in deep zoom, `parameters` then has the center rounded to `f64`.
+    pub deep_view: Option<DeepView>,
+    pub palette: Palette,
+    pub animation: Option<Animation>,
+    pub fps: f64,
+    pub frame_count: Option<u32>,
+}
+
+/// Parses the arguments after the program name. `Ok(None)` means `--help` was shown.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut interactive = false;
+    let mut headless = false;
+    let mut output = None;
+    let mut frames_directory = None;
+    let mut extent = [1024, 1024];
+    let mut device = DeviceChoice::Best;
+    let mut list_devices = false;
+    let mut precision = None;
+    let mut frames_in_flight = 2;
+
+    let mut bookmark: Option<Bookmark> = None;
+    let mut kind = None;
+    let mut center: Option<[String; 2]> = None;
+    let mut scale = None;
+    let mut rotation = None;
+    let mut deep = false;
+    let mut julia_c = None;
+    let mut iterations = None;
+
+    let mut coloring = None;
+    let mut bailout = None;
+    let mut palette = None;
+    let mut palette_offset = None;
+    let mut palette_speed = 0.0;
+    let mut samples = 1;
+
+    let mut animation = None;
+    let mut fps: f64 = 30.0;
+    let mut frame_count = None;
+
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+
+        match arg.as_str() {
+            "--help" | "-h" => {
+                print!("{USAGE}");
+                return Ok(None);
+            }
+            "--interactive" => interactive = true,
+            "--headless" => {
+                headless = true;
+                if let Some(path) = args.next_if(|a| !a.starts_with("--")) {
+                    output = Some(path);
+                }
+            }
+            "--output" => output = Some(value()?),
+            "--frames" => frames_directory = Some(value()?),
+            "--size" => {
+                let value = value()?;
+                let (width, height) = value
+                    .split_once('x')
+                    .ok_or_else(|| format!("--size needs <width>x<height>, got `{value}`"))?;
+                extent = [
+                    parse_number("--size", width)?,
+                    parse_number("--size", height)?,
+                ];
+                if extent.contains(&0) {
+                    return Err("--size needs a positive width and height".to_string());
+                }
+            }
+            "--device" => device = value()?.parse()?,
+            "--list-devices" => list_devices = true,
+            "--precision" => precision = Some(value()?.parse()?),
+            "--frames-in-flight" => {
+                frames_in_flight = parse_number(&arg, &value()?)?;
+                if frames_in_flight == 0 {
+                    return Err("--frames-in-flight needs at least one frame".to_string());
+                }
+            }
+            "--bookmark" => {
+                bookmark = Some(Bookmark::load(value()?).map_err(|e| e.to_string())?);
+            }
+            "--kind" => kind = Some(value()?.parse()?),
+            "--center" => {
+                let [re, im] = split_pair(&arg, &value()?)?;
+                for part in [&re, &im] {
+                    part.parse::<BigFixed>()
+                        .map_err(|e| format!("{arg}: {e}"))?;
+                    // Outside of deep zoom the center is an `f64`
+                    parse_finite::<f64>(&arg, part)?;
+                }
+                center = Some([re, im]);
+            }
+            "--scale" => {
+                let value: f64 = parse_number(&arg, &value()?)?;
+                if !(value > 0.0 && value.is_finite()) {
+                    return Err(format!("--scale needs a positive span, got {value}"));
+                }
+                scale = Some(value);
+            }
+            "--rotation" => rotation = Some(parse_finite::<f64>(&arg, &value()?)? * PI / 180.0),
+            "--deep" => deep = true,
+            "--julia-c" => {
+                let [re, im] = split_pair(&arg, &value()?)?;
+                julia_c = Some([parse_finite(&arg, &re)?, parse_finite(&arg, &im)?]);
+            }
+            "--iterations" => {
+                let value = parse_number(&arg, &value()?)?;
+                if value <= 0 {
+                    return Err("--iterations needs at least one iteration".to_string());
+                }
+                iterations = Some(value);
+            }
+            "--coloring" => coloring = Some(value()?.parse::<Coloring>()?),
+            "--bailout" => {
+                let value: f64 = parse_number(&arg, &value()?)?;
+                if value.is_nan() || value <= 1.0 {
+                    return Err(format!(
+                        "--bailout needs a radius larger than 1, got {value}"
+                    ));
+                }
+                bailout = Some(value);
+            }
+            "--palette" => {
+                palette = Some(Palette::from_name_or_path(&value()?).map_err(|e| e.to_string())?);
+            }
+            "--palette-offset" => palette_offset = Some(parse_finite(&arg, &value()?)?),
+            "--palette-speed" => palette_speed = parse_finite(&arg, &value()?)?,
+            "--samples" => {
+                samples = parse_number(&arg, &value()?)?;
+                if !(1..=MAX_SAMPLES).contains(&samples) {
+                    return Err(format!(
+                        "--samples needs 1 to {MAX_SAMPLES} samples per side, got {samples}"
+                    ));
+                }
+            }
+            "--animation" => {
+                animation = Some(Animation::load(value()?).map_err(|e| e.to_string())?);
+            }
+            "--fps" => {
+                fps = parse_number(&arg, &value()?)?;
+                if !(fps > 0.0 && fps.is_finite()) {
+                    return Err(format!("--fps needs a positive frame rate, got {fps}"));
+                }
+            }
+            "--frame-count" => {
+                let value = parse_number(&arg, &value()?)?;
+                if value == 0 {
+                    return Err("--frame-count needs at least one frame".to_string());
+                }
+                frame_count = Some(value);
+            }
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+
+    let modes = [
+        interactive,
+        frames_directory.is_some(),
+        headless || output.is_some(),
+    ];
+    if !list_devices && modes.iter().filter(|&&mode| mode).count() > 1 {
+        return Err("pick one of --interactive, --output (or --headless) and --frames".to_string());
+    }
+
+    let mode = match (interactive, frames_directory, headless || output.is_some()) {
+        _ if list_devices => Mode::ListDevices,
+        (true, _, _) => Mode::Interactive,
+        (false, Some(directory), _) => Mode::Frames { directory },
+        (false, None, true) => Mode::Headless {
+            output: output.unwrap_or_else(|| "image.png".to_string()),
+        },
+        (false, None, false) => Mode::Interactive,
+    };
+
+    /* Start from the bookmark or the kind's defaults, then apply everything given explicitly */
+    let kind = kind
+        .or(bookmark.as_ref().map(|b| b.kind))
+        .unwrap_or(FractalKind::Julia);
+    let (mut parameters, mut deep_view) = match &bookmark {
+        Some(bookmark) => {
+            let deep_view = bookmark.deep_view().map_err(|e| e.to_string())?;
+            let parameters = bookmark.parameters().map_err(|e| e.to_string())?;
+            (parameters, bookmark.deep.then_some(deep_view))
+        }
+        None => (kind.default_parameters(), None),
+    };
+    let mut view = View::from(&parameters);
+    if let Some(center) = &center {
+        view.center = [
+            parse_number("--center", &center[0])?,
+            parse_number("--center", &center[1])?,
+        ];
+    }
+    view.scale = scale.unwrap_or(view.scale);
+    view.rotation = rotation.unwrap_or(view.rotation);
+    view.apply(&mut parameters);
+
+    /* Deep zoom keeps all the digits of the center */
+    if deep || deep_view.is_some() {
+        let mut deep = match deep_view {
+            Some(deep) => deep,
+            None => DeepView::from_view(&view).ok_or("--deep needs a finite center")?,
+        };
+        if let Some(center) = center {
+            deep.center = [center[0].parse()?, center[1].parse()?];
+        }
+        deep.scale = view.scale;
+        deep.rotation = view.rotation;
+        deep_view = Some(deep);
+    }
+
+    /* Images and frames are rendered without perturbation, which would quietly lose the depth */
+    if deep_view.is_some() && matches!(mode, Mode::Headless { .. } | Mode::Frames { .. }) {
+        return Err(
+            "deep zoom only works in the window, not with --output or --frames".to_string(),
+        );
+    }
+
+    let palette = match palette {
+        Some(palette) => palette,
+        None => match &bookmark {
+            Some(bookmark) => bookmark.palette().map_err(|e| e.to_string())?,
+            None => Palette::built_in("rainbow").unwrap(),
+        },
+    };
+
+    parameters.mouse_pos = julia_c.unwrap_or(match &bookmark {
+        Some(bookmark) => bookmark.julia_c,
+        None => FractalKind::Julia.default_parameters().mouse_pos,
+    });
+    parameters.iterations = iterations.unwrap_or(parameters.iterations);
+    parameters.coloring = coloring.map_or(parameters.coloring, |c| c as i32);
+    parameters.bailout = bailout.unwrap_or(parameters.bailout);
+    parameters.palette_offset = palette_offset.unwrap_or(parameters.palette_offset);
+    parameters.palette_speed = palette_speed;
+    parameters.samples = samples;
+
+    Ok(Some(Options {
+        mode,
+        extent,
+        device,
+        precision,
+        frames_in_flight,
+        kind,
+        parameters,
+        deep_view,
+        palette,
+        animation,
+        fps,
+        frame_count,
+    }))
+}
+
+fn parse_number<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{arg} needs a number, got `{value}`"))
+}
+
+/// Like `parse_number`, but also rejects `nan` and `inf`.
+fn parse_finite<T: FromStr + Into<f64> + Copy>(arg: &str, value: &str) -> Result<T, String> {
+    let number: T = parse_number(arg, value)?;
+    if !number.into().is_finite() {
+        return Err(format!("{arg} needs a finite number, got `{value}`"));
+    }
+    Ok(number)
+}
+
+/// `re,im` into its two halves.
+fn split_pair(arg: &str, value: &str) -> Result<[String; 2], String> {
+    let (re, im) = value
+        .split_once(',')
+        .ok_or_else(|| format!("{arg} needs <re>,<im>, got `{value}`"))?;
+    Ok([re.trim().to_string(), im.trim().to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &str) -> Result<Option<Options>, String> {
+        parse(args.split_whitespace().map(str::to_string))
+    }
+
+    fn options(args: &str) -> Options {
+        parse_args(args).unwrap().unwrap()
+    }
+
+    #[test]
+    fn defaults() {
+        let options = options("");
+        assert!(matches!(options.mode, Mode::Interactive));
+        assert_eq!(options.extent, [1024, 1024]);
+        assert_eq!(options.kind, FractalKind::Julia);
+        assert_eq!(options.parameters.samples, 1);
+        assert_eq!(options.palette.name, "rainbow");
+        assert!(options.deep_view.is_none());
+    }
+
+    #[test]
+    fn help_shows_nothing_else() {
+        assert!(parse_args("--help").unwrap().is_none());
+    }
+
+    #[test]
+    fn modes() {
+        let headless = options("--headless");
+        assert!(matches!(headless.mode, Mode::Headless { output } if output == "image.png"));
+        let output = options("--output poster.png --size 640x480");
+        assert!(matches!(output.mode, Mode::Headless { output } if output == "poster.png"));
+        assert_eq!(output.extent, [640, 480]);
+        let frames = options("--frames out --frame-count 5");
+        assert!(matches!(frames.mode, Mode::Frames { directory } if directory == "out"));
+        assert_eq!(frames.frame_count, Some(5));
+        let list = options("--frames out --list-devices");
+        assert!(matches!(list.mode, Mode::ListDevices));
+    }
+
+    #[test]
+    fn rejects_conflicting_modes() {
+        for args in [
+            "--interactive --frames out",
+            "--interactive --output image.png",
+            "--headless --frames out",
+            "--deep --output image.png",
+            "--deep --frames out",
+        ] {
+            assert!(parse_args(args).is_err(), "{args}");
+        }
+        assert!(options("--deep").deep_view.is_some());
+    }
+
+    #[test]
+    fn applies_the_view() {
+        let options = options("--kind mandelbrot --center -0.5,0.25 --scale 2 --rotation 90");
+        let view = View::from(&options.parameters);
+        assert_eq!(options.kind, FractalKind::Mandelbrot);
+        assert_eq!(view.center, [-0.5, 0.25]);
+        assert_eq!(view.scale, 2.0);
+        assert!((view.rotation - PI / 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rejects_non_finite_numbers() {
+        for args in [
+            "--center nan,0",
+            "--center 1e400,0",
+            "--scale inf",
+            "--rotation nan",
+            "--rotation inf",
+            "--julia-c 0,nan",
+            "--palette-offset inf",
+            "--palette-speed nan",
+            "--bailout nan",
+            "--fps inf",
+        ] {
+            assert!(parse_args(args).is_err(), "{args}");
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_numbers() {
+        for args in [
+            "--size 0x100",
+            "--frames-in-flight 0",
+            "--scale 0",
+            "--iterations 0",
+            "--bailout 1",
+            "--samples 0",
+            "--samples 17",
+            "--fps 0",
+            "--frame-count 0",
+            "--frame-count -1",
+        ] {
+            assert!(parse_args(args).is_err(), "{args}");
+        }
+        assert_eq!(options("--samples 16").parameters.samples, MAX_SAMPLES);
+    }
+
+    #[test]
+    fn rejects_malformed_arguments() {
+        for args in [
+            "--size 100",
+            "--center 0",
+            "--iterations many",
+            "--kind cantor",
+            "--palette",
+            "--unknown",
+        ] {
+            assert!(parse_args(args).is_err(), "{args}");
+        }
+    }
+}
@@ -0,0 +1,34 @@
+// Coloring shared by all fractal shaders. Must match `Coloring` in `coloring.rs`.
+const int COLORING_BANDED = 0;
+const int COLORING_SMOOTH = 1;
+
+// Shaders with more bindings move the palette out of the way
+#ifndef PALETTE_BINDING
+#define PALETTE_BINDING 2
+#endif
+
+// Cyclic gradient from `palette.rs`, sampled with repeat addressing
+layout(set = 0, binding = PALETTE_BINDING) uniform sampler1D palette;
+
+// `i` is the iteration `z` escaped the `bailout` radius at (or `maxIterations` if it never did),
+// `minDist` the closest the orbit got to the origin. `palette_shift` moves along the palette.
+vec4 fractal_color(int i, int maxIterations, rvec2 z, real minDist, real bailout, int coloring, float palette_shift) {
+    real hue = real(i) / real(maxIterations); // double(tempDist);
+
+    if (coloring == COLORING_SMOOTH && i < maxIterations) {
+        // Normalized iteration count: continuous across bands, as |z| goes from bailout to
+        // bailout^2 in one iteration. Logs are only available for floats.
+        float ratio = log(float(length(z))) / log(float(bailout));
+        hue = (real(i) - real(log2(ratio))) / real(maxIterations);
+    }
+
+    real value = 1.0 - real(minDist);
+
+    if (maxIterations == i) {
+        value = 0.0;
+    }
+
+    vec3 rgb = texture(palette, float(hue) + palette_shift).rgb * float(value);
+
+    return vec4(rgb, 1.0);
+}
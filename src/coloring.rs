@@ -0,0 +1,63 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Escape radius used unless configured otherwise. Smooth coloring needs it well above 2.
+pub const DEFAULT_BAILOUT: f64 = 256.0;
+
+/// How escaped points are colored, `Parameters::coloring` in the shaders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Coloring {
+    /// Hue from the raw iteration count, which shows visible bands.
+    Banded = 0,
+    /// Hue from the normalized (continuous) iteration count, using how far past the bailout
+    /// radius `z` landed.
+    Smooth = 1,
+}
+
+impl Coloring {
+    pub const ALL: [Coloring; 2] = [Coloring::Banded, Coloring::Smooth];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Coloring::Banded => "banded",
+            Coloring::Smooth => "smooth",
+        }
+    }
+
+    /// The mode for a `Parameters::coloring` value.
+    pub fn from_i32(value: i32) -> Option<Coloring> {
+        Coloring::ALL.into_iter().find(|&c| c as i32 == value)
+    }
+
+    /// The mode after this one, wrapping around. Used to cycle through modes with a key.
+    pub fn next(self) -> Coloring {
+        let index = Coloring::ALL.iter().position(|&c| c == self).unwrap();
+        Coloring::ALL[(index + 1) % Coloring::ALL.len()]
+    }
+}
+
+impl fmt::Display for Coloring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for Coloring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Coloring::ALL
+            .into_iter()
+            .find(|coloring| coloring.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = Coloring::ALL.iter().map(|c| c.name()).collect();
+                format!(
+                    "unknown coloring `{s}`, expected one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
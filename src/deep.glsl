@@ -0,0 +1,109 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+// Perturbation only makes sense in double precision, the shared includes use it too
+#include "precision.glsl"
+
+// Deep zoom with perturbation theory. Instead of iterating z itself, which needs more precision
+// than a double has, every pixel iterates its difference `dz` to a reference orbit computed in
+// arbitrary precision on the CPU (see `deep.rs`):
+//
+//   dz' = 2 Z dz + dz^2 + dc
+//
+// The first `series_skip` iterations come from a series approximation in the pixel offset.
+
+struct DeepParameters {
+    dvec2 series_a;
+    dvec2 series_b;
+    dvec2 series_c;
+    dvec2 axis_x;
+    dvec2 axis_y;
+    double radius;
+    double bailout;
+    double time;
+    int iterations;
+    int series_skip;
+    int orbit_length;
+    int julia;
+    int coloring;
+    int samples;
+    float palette_offset;
+    float palette_speed;
+};
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+
+layout(std140, binding = 1) readonly buffer DeepParametersIn {
+    DeepParameters p;
+};
+
+layout(std430, binding = 2) readonly buffer ReferenceOrbit {
+    dvec2 orbit[];
+};
+
+dvec2 cmul(dvec2 a, dvec2 b) {
+    return dvec2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+#define PALETTE_BINDING 3
+#include "color.glsl"
+#include "sampling.glsl"
+
+vec4 sample_color(dvec2 coordinates) {
+    // Offset from the view center, which is where the reference orbit starts
+    const dvec2 offset = coordinates.x * p.axis_x + coordinates.y * p.axis_y;
+
+    // Normalized offset for the series, |u| <= 1 over the whole view
+    const dvec2 u = offset / p.radius;
+    dvec2 dz = cmul(p.series_a, u) + cmul(p.series_b, cmul(u, u)) + cmul(p.series_c, cmul(u, cmul(u, u)));
+
+    // Julia sets perturb the starting point, the Mandelbrot set perturbs c
+    const dvec2 dc = p.julia != 0 ? dvec2(0.0) : offset;
+
+    // orbit trap coloring
+    double minDist = 1e20;
+    double tempDist = 1e20;
+
+    const int maxIterations = p.iterations;
+
+    int n = p.series_skip;
+    dvec2 z = orbit[n] + dz;
+    int i;
+    for (i = p.series_skip; i < maxIterations; i += 1) {
+        dz = 2.0 * cmul(orbit[n], dz) + cmul(dz, dz) + dc;
+        n += 1;
+
+        z = orbit[n] + dz;
+
+        tempDist = length(z);
+        if (minDist > tempDist) {
+            minDist = tempDist;
+        }
+
+        if (length(z) > p.bailout) {
+            break;
+        }
+
+        // Rebase onto the start of the orbit when the pixel gets closer to it than to the
+        // reference (where dz loses precision), or when the reference runs out.
+        if (length(z) < length(dz) || n == p.orbit_length - 1) {
+            dz = z - orbit[0];
+            n = 0;
+        }
+    }
+
+    const float palette_shift = p.palette_offset + float(p.time) * p.palette_speed;
+    return fractal_color(i, maxIterations, z, minDist, p.bailout, p.coloring, palette_shift);
+}
+
+void main() {
+    // The dispatch is rounded up to whole workgroups, skip invocations outside the image
+    if (any(greaterThanEqual(gl_GlobalInvocationID.xy, uvec2(imageSize(img))))) {
+        return;
+    }
+
+    vec4 to_write = supersample(p.samples);
+
+    imageStore(img, ivec2(gl_GlobalInvocationID.xy), to_write);
+}
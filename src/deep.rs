@@ -0,0 +1,307 @@
+use crate::bigfloat::BigFixed;
+use crate::cs;
+use crate::error::FractalError;
+use crate::fractal::FractalKind;
+use crate::view::{square_coordinates, View};
+
+/// Third order series approximation only: once the third order term stops being negligible
+/// compared to the second, the remaining iterations are done per pixel.
+const SERIES_TOLERANCE: f64 = 1e-3;
+
+/// Escape radius of the reference orbit, past it the orbit ends and pixels rebase onto its start.
+const REFERENCE_BAILOUT: f64 = 1e10;
+
+/// A view that can be zoomed in beyond `f64` precision.
+///
+/// The center is stored with arbitrary precision. Interactive code keeps working with an `f64`
+/// [`View`] whose center is an *offset* from this center, and folds it in with
+/// [`DeepView::recenter`] before every frame, so offsets never grow past a few screens.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeepView {
+    pub center: [BigFixed; 2],
+    pub scale: f64,
+    pub rotation: f64,
+}
+
+impl DeepView {
+    /// `None` when the center of `view` isn't finite.
+    pub fn from_view(view: &View) -> Option<Self> {
+        let bits = precision_bits(view.scale);
+        Some(DeepView {
+            center: [
+                BigFixed::from_f64(view.center[0], bits)?,
+                BigFixed::from_f64(view.center[1], bits)?,
+            ],
+            scale: view.scale,
+            rotation: view.rotation,
+        })
+    }
+
+    /// Adds the offset in `view.center` to the high precision center and resets it to zero.
+    /// Also picks up the scale and rotation, and grows the precision to match the scale. An
+    /// offset that isn't finite is dropped, leaving the center where it was.
+    pub fn recenter(&mut self, view: &mut View) {
+        let bits = precision_bits(view.scale);
+        for axis in 0..2 {
+            let center = self.center[axis].with_bits(bits);
+            self.center[axis] = match BigFixed::from_f64(view.center[axis], bits) {
+                Some(offset) => &center + &offset,
+                None => center,
+            };
+        }
+        self.scale = view.scale;
+        self.rotation = view.rotation;
+
+        view.center = [0.0, 0.0];
+    }
+
+    /// The same view with the center rounded to `f64`, for when leaving deep zoom.
+    pub fn to_view(&self) -> View {
+        View {
+            center: [self.center[0].to_f64(), self.center[1].to_f64()],
+            scale: self.scale,
+            rotation: self.rotation,
+        }
+    }
+}
+
+/// Bits after the point needed to resolve pixels at `scale`, with margin for the orbit. A scale
+/// of zero gets as many as the smallest `f64` needs, one that isn't a positive number the least.
+pub fn precision_bits(scale: f64) -> u32 {
+    // The smallest `f64` is 2^-1074
+    const MAX_BITS: f64 = 64.0 + 1074.0;
+
+    let needed = 64.0 - scale.log2().min(0.0);
+    (needed.min(MAX_BITS) as u32).next_multiple_of(32)
+}
+
+/// Everything the perturbation shader needs, computed on the CPU.
+///
+/// The reference orbit `Z_n` is iterated at the view center in arbitrary precision and stored
+/// rounded to `f64`, which is enough because pixels only iterate their (small) difference to it:
+///
+/// `δ_{n+1} = 2 Z_n δ_n + δ_n² + δc`
+///
+/// The first `series_skip` iterations are skipped altogether with a series approximation
+/// `δ_n ≈ A_n u + B_n u² + C_n u³` in the pixel offset `u`, normalized so the view fits in
+/// `|u| <= 1`.
+pub struct DeepReference {
+    pub orbit: Vec<[f64; 2]>,
+    pub parameters: cs::DeepParameters,
+}
+
+impl DeepReference {
+    /// Takes the iteration count, coloring, samples, palette and Julia constant from
+    /// `parameters`, its center and axes are ignored in favor of `view`. `extent` is the size of
+    /// the image that will be rendered, in pixels.
+    ///
+    /// Fails with [`FractalError::InvalidParameters`] unless the iteration count is positive.
+    pub fn new(
+        kind: FractalKind,
+        view: &DeepView,
+        parameters: &cs::Parameters,
+        extent: [u32; 2],
+    ) -> Result<Self, FractalError> {
+        let iterations = parameters.iterations;
+        if iterations <= 0 {
+            return Err(FractalError::InvalidParameters(format!(
+                "{iterations} iterations, there must be at least one"
+            )));
+        }
+        let julia_c = parameters.mouse_pos;
+
+        let bits = view.center[0].bits().max(view.center[1].bits());
+        let escape = BigFixed::from_f64(REFERENCE_BAILOUT, bits).expect("the bailout is finite");
+        // A constant that isn't finite doesn't render anything sensible either way
+        let julia_c =
+            julia_c.map(|c| BigFixed::from_f64(c, bits).unwrap_or_else(|| BigFixed::zero(bits)));
+
+        let (mut z, c) = match kind {
+            FractalKind::Julia => (view.center.clone(), julia_c),
+            FractalKind::Mandelbrot => (
+                [BigFixed::zero(bits), BigFixed::zero(bits)],
+                view.center.clone(),
+            ),
+        };
+
+        // Most orbits escape long before the iteration count, so the orbit grows as it goes
+        let mut orbit = vec![];
+        orbit.push([z[0].to_f64(), z[1].to_f64()]);
+
+        for _ in 0..iterations {
+            let re = &(&(&z[0] * &z[0]) - &(&z[1] * &z[1])) + &c[0];
+            let two_re = &z[0] + &z[0];
+            let im = &(&two_re * &z[1]) + &c[1];
+            z = [re, im];
+
+            orbit.push([z[0].to_f64(), z[1].to_f64()]);
+
+            let magnitude = &(&z[0] * &z[0]) + &(&z[1] * &z[1]);
+            if !(&magnitude - &escape).is_negative() {
+                break;
+            }
+        }
+
+        // The largest pixel offset is the corner of the view.
+        let size = [extent[0] as f64, extent[1] as f64];
+        let corner = square_coordinates([0.0, 0.0], size);
+        let radius = view.scale * corner[0].hypot(corner[1]);
+        let [axis_x, axis_y] = View {
+            center: [0.0, 0.0],
+            scale: view.scale,
+            rotation: view.rotation,
+        }
+        .axes();
+        let (series_skip, series) = series_approximation(kind, &orbit, radius);
+
+        let parameters = cs::DeepParameters {
+            series_a: series[0],
+            series_b: series[1],
+            series_c: series[2],
+            axis_x,
+            axis_y,
+            radius,
+            bailout: parameters.bailout,
+            time: parameters.time,
+            iterations,
+            series_skip,
+            orbit_length: orbit.len() as i32,
+            julia: (kind == FractalKind::Julia) as i32,
+            coloring: parameters.coloring,
+            samples: parameters.samples,
+            palette_offset: parameters.palette_offset,
+            palette_speed: parameters.palette_speed,
+        };
+
+        Ok(DeepReference { orbit, parameters })
+    }
+}
+
+/// Finds how many iterations the series can skip, and its (normalized) coefficients there.
+///
+/// For the Mandelbrot set `δ_0 = 0` and every iteration adds `δc = u * radius`; for Julia sets
+/// `δ_0 = u * radius` and nothing is added. With `a`, `b`, `c` already multiplied by `radius`,
+/// `radius²` and `radius³` they stay in `f64` range no matter how deep the zoom goes.
+fn series_approximation(
+    kind: FractalKind,
+    orbit: &[[f64; 2]],
+    radius: f64,
+) -> (i32, [[f64; 2]; 3]) {
+    let (mut a, added) = match kind {
+        FractalKind::Julia => ([radius, 0.0], 0.0),
+        FractalKind::Mandelbrot => ([0.0, 0.0], radius),
+    };
+    let mut b = [0.0, 0.0];
+    let mut c = [0.0, 0.0];
+
+    let mut skip = 0;
+    let mut coefficients = [a, b, c];
+
+    // Stop short of the end of the orbit so pixels always have some reference left to iterate.
+    for (n, z) in orbit.iter().enumerate().take(orbit.len().saturating_sub(2)) {
+        let two_z = [2.0 * z[0], 2.0 * z[1]];
+
+        let next_a = add(mul(two_z, a), [added, 0.0]);
+        let next_b = add(mul(two_z, b), mul(a, a));
+        let next_c = add(mul(two_z, c), mul([2.0 * a[0], 2.0 * a[1]], b));
+
+        a = next_a;
+        b = next_b;
+        c = next_c;
+
+        let accurate = norm(c) <= SERIES_TOLERANCE * norm(b);
+        if !accurate || !norm(a).is_finite() {
+            break;
+        }
+
+        skip = n as i32 + 1;
+        coefficients = [a, b, c];
+    }
+
+    (skip, coefficients)
+}
+
+fn add(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn mul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+fn norm(a: [f64; 2]) -> f64 {
+    a[0].hypot(a[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precision_grows_with_zoom() {
+        assert_eq!(precision_bits(4.0), 64);
+        assert_eq!(precision_bits(1.0), 64);
+        assert_eq!(precision_bits(1e-10), 128);
+        assert!(precision_bits(1e-300) > precision_bits(1e-100));
+    }
+
+    #[test]
+    fn precision_of_degenerate_scales() {
+        assert_eq!(precision_bits(0.0), 1152);
+        assert_eq!(precision_bits(f64::MIN_POSITIVE * f64::EPSILON), 1152);
+        assert_eq!(precision_bits(f64::INFINITY), 64);
+        assert_eq!(precision_bits(f64::NAN), 64);
+        assert_eq!(precision_bits(-1.0), 64);
+    }
+
+    #[test]
+    fn from_view_rejects_non_finite_centers() {
+        let view = View {
+            center: [f64::NAN, 0.0],
+            scale: 1.0,
+            rotation: 0.0,
+        };
+        assert!(DeepView::from_view(&view).is_none());
+    }
+
+    #[test]
+    fn recenter_drops_non_finite_offsets() {
+        let mut view = View {
+            center: [0.5, -0.25],
+            scale: 1e-20,
+            rotation: 0.0,
+        };
+        let mut deep = DeepView::from_view(&view).unwrap();
+        view.center = [f64::INFINITY, 0.25];
+        deep.recenter(&mut view);
+        assert_eq!(deep.to_view().center, [0.5, 0.0]);
+        assert_eq!(view.center, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn reference_needs_iterations() {
+        let deep = DeepView::from_view(&FractalKind::Mandelbrot.overview()).unwrap();
+        let mut parameters = FractalKind::Mandelbrot.default_parameters();
+        for iterations in [0, -1, i32::MIN] {
+            parameters.iterations = iterations;
+            let reference = DeepReference::new(FractalKind::Mandelbrot, &deep, &parameters, [8, 8]);
+            assert!(matches!(reference, Err(FractalError::InvalidParameters(_))));
+        }
+    }
+
+    #[test]
+    fn escaping_orbits_stop_early() {
+        let view = View {
+            center: [2.0, 2.0],
+            scale: 1.0,
+            rotation: 0.0,
+        };
+        let deep = DeepView::from_view(&view).unwrap();
+        let mut parameters = FractalKind::Mandelbrot.default_parameters();
+        parameters.iterations = i32::MAX;
+        let reference =
+            DeepReference::new(FractalKind::Mandelbrot, &deep, &parameters, [8, 8]).unwrap();
+        assert!(reference.orbit.len() < 10);
+        assert_eq!(reference.parameters.iterations, i32::MAX);
+    }
+}
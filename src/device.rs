@@ -0,0 +1,325 @@
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
+use vulkano::device::{
+    Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo, QueueFlags,
+};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::swapchain::Surface;
+use vulkano::{Version, VulkanLibrary};
+
+use crate::error::FractalError;
+
+/// Which physical device [`select_device`] uses, parsed from `cpu`, an index or a name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DeviceChoice {
+    /// The fastest looking suitable device, discrete GPUs first.
+    #[default]
+    Best,
+    /// The device at this position in `enumerate_physical_devices`, see [`device_report`].
+    Index(usize),
+    /// The first device whose name contains this, ignoring case.
+    Name(String),
+    /// A software implementation like lavapipe or SwiftShader, for testing.
+    Cpu,
+}
+
+impl DeviceChoice {
+    fn matches(&self, index: usize, device: &PhysicalDevice) -> bool {
+        match self {
+            DeviceChoice::Best => true,
+            DeviceChoice::Index(i) => *i == index,
+            DeviceChoice::Name(name) => device
+                .properties()
+                .device_name
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+            DeviceChoice::Cpu => device.properties().device_type == PhysicalDeviceType::Cpu,
+        }
+    }
+}
+
+impl fmt::Display for DeviceChoice {
+    /// The `--device` argument that parses back into this choice.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceChoice::Best => f.pad("best"),
+            DeviceChoice::Index(index) => f.pad(&index.to_string()),
+            DeviceChoice::Name(name) => f.pad(name),
+            DeviceChoice::Cpu => f.pad("cpu"),
+        }
+    }
+}
+
+impl FromStr for DeviceChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("empty device name".to_string());
+        }
+        Ok(match s {
+            "best" => DeviceChoice::Best,
+            "cpu" => DeviceChoice::Cpu,
+            _ => match s.parse() {
+                Ok(index) => DeviceChoice::Index(index),
+                Err(_) => DeviceChoice::Name(s.to_string()),
+            },
+        })
+    }
+}
+
+/// The queues made by [`select_device`]. `compute` runs the fractal shaders, `present` blits to
+/// and presents the swapchain.
+///
+/// They are the same queue unless the device has a queue family for compute but not graphics,
+/// which many GPUs can run alongside the graphics queue (async compute).
+#[derive(Clone, Debug)]
+pub struct Queues {
+    pub compute: Arc<Queue>,
+    pub present: Arc<Queue>,
+}
+
+impl Queues {
+    /// Whether compute and present are the same queue, so everything can go in one command buffer.
+    pub fn is_shared(&self) -> bool {
+        Arc::ptr_eq(&self.compute, &self.present)
+    }
+}
+
+/// An instance without any surface extensions, for rendering and querying devices without a
+/// display.
+pub fn headless_instance() -> Result<Arc<Instance>, FractalError> {
+    let library = VulkanLibrary::new()?;
+    Ok(Instance::new(library, InstanceCreateInfo::default())?)
+}
+
+/// One paragraph per physical device: its index (for [`DeviceChoice::Index`]), name, type, API
+/// version, whether it has `shader_float64` and its queue families.
+pub fn device_report(instance: &Arc<Instance>) -> Result<String, FractalError> {
+    let mut report = String::new();
+
+    // Writing to a `String` can't fail
+    for (index, device) in instance.enumerate_physical_devices()?.enumerate() {
+        let properties = device.properties();
+        writeln!(report, "{index}: {}", properties.device_name).unwrap();
+        writeln!(report, "    type: {:?}", properties.device_type).unwrap();
+        writeln!(report, "    api version: {}", device.api_version()).unwrap();
+        writeln!(
+            report,
+            "    shader_float64: {}",
+            if device.supported_features().shader_float64 {
+                "yes"
+            } else {
+                "no"
+            }
+        )
+        .unwrap();
+
+        for (family, queue) in device.queue_family_properties().iter().enumerate() {
+            writeln!(
+                report,
+                "    queue family {family}: {} queues, {:?}",
+                queue.queue_count, queue.queue_flags
+            )
+            .unwrap();
+        }
+    }
+
+    if report.is_empty() {
+        report.push_str("no physical devices found\n");
+    }
+    Ok(report)
+}
+
+/// Picks the best physical device with a compute queue family and creates a logical device.
+///
+/// When a `surface` is given, the queue family must also be able to present to it, and a queue
+/// family with compute but without graphics gets a second queue for rendering, see [`Queues`].
+/// Headless rendering passes `None` and gets a single queue. Only devices matching `choice` are
+/// considered.
+///
+/// Fails with [`FractalError::NoDevice`] when no device matches.
+///
+/// `shader_float64` is enabled when the device has it, see [`Precision`].
+///
+/// [`Precision`]: crate::Precision
+pub fn select_device(
+    instance: Arc<Instance>,
+    mut device_extensions: DeviceExtensions,
+    surface: Option<&Arc<Surface>>,
+    choice: &DeviceChoice,
+) -> Result<(Arc<Device>, Queues), FractalError> {
+    let (physical_device, queue_family_index) = instance
+        .enumerate_physical_devices()?
+        .enumerate()
+        .filter(|(i, p)| choice.matches(*i, p))
+        .map(|(_, p)| p)
+        .filter(|p| {
+            // For this example, we require at least Vulkan 1.3, or a device that has the
+            // `khr_dynamic_rendering` extension available.
+            p.api_version() >= Version::V1_3 || p.supported_extensions().khr_dynamic_rendering
+        })
+        .filter(|p| {
+            // Some devices may not support the extensions or features that your application, or
+            // report properties and limits that are not sufficient for your application. These
+            // should be filtered out here.
+            p.supported_extensions().contains(&device_extensions)
+        })
+        .filter_map(|p| {
+            // For each physical device, we try to find a suitable queue family that will execute
+            // our draw commands.
+            //
+            // Devices can provide multiple queues to run commands in parallel (for example a draw
+            // queue and a compute queue), similar to CPU threads. This is something you have to
+            // have to manage manually in Vulkan. Queues of the same type belong to the same queue
+            // family.
+            //
+            // Here, we look for a single queue family that is suitable for our purposes. In a
+            // real-world application, you may want to use a separate dedicated transfer queue to
+            // handle data transfers in parallel with graphics operations. You may also need a
+            // separate queue for compute operations, if your application uses those.
+            p.queue_family_properties()
+                .iter()
+                .enumerate()
+                .position(|(i, q)| {
+                    // We select a queue family that supports compute operations. When drawing to
+                    // a window surface we also need to check that queues in this queue family are
+                    // capable of presenting images to the surface. Headless rendering has no
+                    // surface, so any compute queue will do.
+                    q.queue_flags.intersects(QueueFlags::COMPUTE)
+                        && surface.is_none_or(|surface| {
+                            p.surface_support(i as u32, surface).unwrap_or(false)
+                        })
+                })
+                // The code here searches for the first queue family that is suitable. If none is
+                // found, `None` is returned to `filter_map`, which disqualifies this physical
+                // device.
+                .map(|i| (p, i as u32))
+        })
+        // All the physical devices that pass the filters above are suitable for the application.
+        // However, not every device is equal, some are preferred over others. Now, we assign each
+        // physical device a score, and pick the device with the lowest ("best") score.
+        //
+        // In this example, we simply select the best-scoring device to use in the application.
+        // In a real-world setting, you may want to use the best-scoring device only as a "default"
+        // or "recommended" device, and let the user choose the device themself.
+        .min_by_key(|(p, _)| {
+            // We assign a lower score to device types that are likely to be faster/better.
+            match p.properties().device_type {
+                PhysicalDeviceType::DiscreteGpu => 0,
+                PhysicalDeviceType::IntegratedGpu => 1,
+                PhysicalDeviceType::VirtualGpu => 2,
+                PhysicalDeviceType::Cpu => 3,
+                PhysicalDeviceType::Other => 4,
+                _ => 5,
+            }
+        })
+        .ok_or_else(|| FractalError::NoDevice(choice.clone()))?;
+
+    if physical_device.api_version() < Version::V1_3 {
+        device_extensions.khr_dynamic_rendering = true;
+    }
+
+    // Some little debug infos.
+    println!(
+        "Using device: {} (type: {:?})",
+        physical_device.properties().device_name,
+        physical_device.properties().device_type,
+    );
+
+    // If the selected device doesn't have Vulkan 1.3 available, then we need to enable the
+    // `khr_dynamic_rendering` extension manually. This extension became a core part of Vulkan
+    // in version 1.3 and later, so it's always available then and it does not need to be enabled.
+    // We can be sure that this extension will be available on the selected physical device,
+    // because we filtered out unsuitable devices in the device selection code above.
+    if physical_device.api_version() < Version::V1_3 {
+        device_extensions.khr_dynamic_rendering = true;
+    }
+
+    let shader_float64 = physical_device.supported_features().shader_float64;
+
+    /* A compute only family runs alongside the graphics one, but only a window has anything to
+     * do on the graphics queue meanwhile */
+    let compute_family_index = surface
+        .and_then(|_| {
+            physical_device
+                .queue_family_properties()
+                .iter()
+                .position(|q| {
+                    q.queue_flags.intersects(QueueFlags::COMPUTE)
+                        && !q.queue_flags.intersects(QueueFlags::GRAPHICS)
+                })
+        })
+        .map(|i| i as u32)
+        .filter(|&i| i != queue_family_index);
+
+    let mut queue_create_infos = vec![QueueCreateInfo {
+        queue_family_index,
+        ..Default::default()
+    }];
+    if let Some(queue_family_index) = compute_family_index {
+        queue_create_infos.push(QueueCreateInfo {
+            queue_family_index,
+            ..Default::default()
+        });
+    }
+
+    let (device, mut queues) = Device::new(
+        // Which physical device to connect to.
+        physical_device,
+        DeviceCreateInfo {
+            // The list of queues that we are going to use. Here we use one queue from the
+            // previously chosen queue family, and one from the compute family if there is one.
+            queue_create_infos,
+
+            // A list of optional features and extensions that our program needs to work correctly.
+            // Some parts of the Vulkan specs are optional and must be enabled manually at device
+            // creation. In this example the only things we are going to need are the
+            // `khr_swapchain` extension that allows us to draw to a window, and
+            // `khr_dynamic_rendering` if we don't have Vulkan 1.3 available.
+            enabled_extensions: device_extensions,
+
+            // In order to render with Vulkan 1.3's dynamic rendering, we need to enable it here.
+            // Otherwise, we are only allowed to render with a render pass object, as in the
+            // standard triangle example. The feature is required to be supported by the device if
+            // it supports Vulkan 1.3 and higher, or if the `khr_dynamic_rendering` extension is
+            // available, so we don't need to check for support.
+            //
+            // Doubles are optional, devices without them get the single precision shaders.
+            enabled_features: Features {
+                shader_float64,
+                dynamic_rendering: true,
+                ..Features::empty()
+            },
+
+            ..Default::default()
+        },
+    )?;
+
+    // The queues come in the order they were asked for
+    let present = queues.next().unwrap();
+    let compute = queues.next().unwrap_or_else(|| present.clone());
+    Ok((device, Queues { compute, present }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choices_round_trip() {
+        for choice in [
+            DeviceChoice::Best,
+            DeviceChoice::Index(3),
+            DeviceChoice::Name("GeForce".to_string()),
+            DeviceChoice::Cpu,
+        ] {
+            assert_eq!(choice.to_string().parse(), Ok(choice));
+        }
+        assert_eq!(format!("{:>5}", DeviceChoice::Cpu), "  cpu");
+        assert!("".parse::<DeviceChoice>().is_err());
+    }
+}
@@ -0,0 +1,70 @@
+// Double-float ("df64") arithmetic: a value is the unevaluated sum `hi + lo` of two floats,
+// stored as vec2(hi, lo), for about 48 bits of mantissa on GPUs with slow or no doubles.
+// Based on the error free transformations of Dekker and Knuth. `precise` keeps the compiler from
+// reassociating or fusing the operations, which would lose exactly the error terms kept in `lo`.
+
+vec2 quick_two_sum(float a, float b) {
+    precise float s = a + b;
+    precise float e = b - (s - a);
+    return vec2(s, e);
+}
+
+vec2 two_sum(float a, float b) {
+    precise float s = a + b;
+    precise float v = s - a;
+    precise float e = (a - (s - v)) + (b - v);
+    return vec2(s, e);
+}
+
+// Splits a float into two halves of 12 bits, so their products are exact. Vulkan doesn't promise
+// that `fma` is fused, so it can't be used to get the product's error.
+vec2 split(float a) {
+    precise float t = 4097.0 * a;
+    precise float hi = t - (t - a);
+    precise float lo = a - hi;
+    return vec2(hi, lo);
+}
+
+vec2 two_prod(float a, float b) {
+    precise float p = a * b;
+    const vec2 as = split(a);
+    const vec2 bs = split(b);
+    precise float e = ((as.x * bs.x - p) + as.x * bs.y + as.y * bs.x) + as.y * bs.y;
+    return vec2(p, e);
+}
+
+vec2 df64_add(vec2 a, vec2 b) {
+    precise vec2 s = two_sum(a.x, b.x);
+    precise vec2 t = two_sum(a.y, b.y);
+    s.y += t.x;
+    s = quick_two_sum(s.x, s.y);
+    s.y += t.y;
+    return quick_two_sum(s.x, s.y);
+}
+
+vec2 df64_sub(vec2 a, vec2 b) {
+    return df64_add(a, -b);
+}
+
+vec2 df64_mul(vec2 a, vec2 b) {
+    precise vec2 p = two_prod(a.x, b.x);
+    p.y += a.x * b.y + a.y * b.x;
+    return quick_two_sum(p.x, p.y);
+}
+
+// Complex numbers are vec4(re.hi, re.lo, im.hi, im.lo)
+
+// z^2 + c
+vec4 df64_complex_sqr_add(vec4 z, vec4 c) {
+    const vec2 re = df64_sub(df64_mul(z.xy, z.xy), df64_mul(z.zw, z.zw));
+    const vec2 im = df64_mul(z.xy, z.zw);
+    return vec4(df64_add(re, c.xy), df64_add(df64_add(im, im), c.zw));
+}
+
+// `offset` added to the point `hi + lo`, an offset from the view center is small enough for floats
+vec4 df64_complex_offset(vec2 hi, vec2 lo, vec2 offset) {
+    return vec4(
+        df64_add(vec2(hi.x, lo.x), vec2(offset.x, 0.0)),
+        df64_add(vec2(hi.y, lo.y), vec2(offset.y, 0.0))
+    );
+}
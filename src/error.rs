@@ -0,0 +1,170 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use vulkano::buffer::AllocateBufferError;
+use vulkano::command_buffer::CommandBufferExecError;
+use vulkano::image::AllocateImageError;
+use vulkano::{LoadingError, Validated, ValidationError, VulkanError};
+
+use crate::device::DeviceChoice;
+
+/// Everything that can go wrong setting up Vulkan and rendering.
+#[derive(Debug)]
+pub enum FractalError {
+    /// The Vulkan library couldn't be loaded, usually because no driver is installed.
+    Loading(LoadingError),
+    /// No physical device matches the choice and has a suitable queue family.
+    NoDevice(DeviceChoice),
+    /// The device lacks a feature, e.g. `shader_float64` for deep zoom.
+    MissingFeature {
+        feature: &'static str,
+        needed_for: String,
+    },
+    /// A shader module or compute pipeline couldn't be created.
+    ShaderLoad(Box<dyn Error + Send + Sync>),
+    /// A buffer or image couldn't be allocated, or read back from the host.
+    Allocation(Box<dyn Error + Send + Sync>),
+    /// The window's surface is gone and can't be presented to anymore.
+    SwapchainLost(VulkanError),
+    /// Commands couldn't be submitted to the queue.
+    Submit(CommandBufferExecError),
+    /// Any other Vulkan call failed.
+    Vulkan(VulkanError),
+    /// Vulkano rejected a call before it reached the driver, which is a bug in this crate.
+    Validation(Box<ValidationError>),
+    /// Render parameters out of range, e.g. no iterations at all.
+    InvalidParameters(String),
+    /// A palette can't be loaded or uploaded, because it's malformed, empty or wider than the
+    /// device allows.
+    InvalidPalette(String),
+    /// A bookmark can't be loaded, or doesn't describe a view that can be rendered.
+    InvalidBookmark(String),
+    /// A keyframe animation can't be loaded, or has keyframes out of range.
+    InvalidAnimation(String),
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// A PNG couldn't be encoded.
+    Encoding(png::EncodingError),
+}
+
+impl fmt::Display for FractalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FractalError::Loading(e) => write!(f, "failed to load the Vulkan library: {e}"),
+            FractalError::NoDevice(DeviceChoice::Best) => {
+                write!(f, "no suitable physical device found")
+            }
+            FractalError::NoDevice(choice) => {
+                write!(
+                    f,
+                    "no suitable physical device matches `{choice}`, see --list-devices"
+                )
+            }
+            FractalError::MissingFeature {
+                feature,
+                needed_for,
+            } => {
+                write!(
+                    f,
+                    "{needed_for} needs {feature}, which this device doesn't have"
+                )
+            }
+            FractalError::ShaderLoad(e) => write!(f, "failed to create a compute pipeline: {e}"),
+            FractalError::Allocation(e) => write!(f, "failed to allocate GPU memory: {e}"),
+            FractalError::SwapchainLost(e) => write!(f, "lost the window's swapchain: {e}"),
+            FractalError::Submit(e) => write!(f, "failed to submit commands: {e}"),
+            FractalError::Vulkan(e) => write!(f, "Vulkan error: {e}"),
+            FractalError::Validation(e) => write!(f, "invalid Vulkan usage: {e}"),
+            FractalError::InvalidParameters(e) => write!(f, "invalid parameters: {e}"),
+            FractalError::InvalidPalette(e) => write!(f, "invalid palette: {e}"),
+            FractalError::InvalidBookmark(e) => write!(f, "invalid bookmark: {e}"),
+            FractalError::InvalidAnimation(e) => write!(f, "invalid animation: {e}"),
+            FractalError::Io(e) => write!(f, "{e}"),
+            FractalError::Encoding(e) => write!(f, "failed to write PNG: {e}"),
+        }
+    }
+}
+
+impl Error for FractalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FractalError::Loading(e) => Some(e),
+            FractalError::ShaderLoad(e) | FractalError::Allocation(e) => Some(e.as_ref()),
+            FractalError::SwapchainLost(e) | FractalError::Vulkan(e) => Some(e),
+            FractalError::Submit(e) => Some(e),
+            FractalError::Validation(e) => Some(e.as_ref()),
+            FractalError::Io(e) => Some(e),
+            FractalError::Encoding(e) => Some(e),
+            FractalError::NoDevice(_)
+            | FractalError::MissingFeature { .. }
+            | FractalError::InvalidParameters(_)
+            | FractalError::InvalidPalette(_)
+            | FractalError::InvalidBookmark(_)
+            | FractalError::InvalidAnimation(_) => None,
+        }
+    }
+}
+
+impl From<LoadingError> for FractalError {
+    fn from(e: LoadingError) -> Self {
+        FractalError::Loading(e)
+    }
+}
+
+impl From<VulkanError> for FractalError {
+    fn from(e: VulkanError) -> Self {
+        FractalError::Vulkan(e)
+    }
+}
+
+impl From<Box<ValidationError>> for FractalError {
+    fn from(e: Box<ValidationError>) -> Self {
+        FractalError::Validation(e)
+    }
+}
+
+impl From<Validated<VulkanError>> for FractalError {
+    fn from(e: Validated<VulkanError>) -> Self {
+        match e {
+            Validated::Error(e) => FractalError::Vulkan(e),
+            Validated::ValidationError(e) => FractalError::Validation(e),
+        }
+    }
+}
+
+impl From<Validated<AllocateBufferError>> for FractalError {
+    fn from(e: Validated<AllocateBufferError>) -> Self {
+        match e {
+            Validated::Error(e) => FractalError::Allocation(Box::new(e)),
+            Validated::ValidationError(e) => FractalError::Validation(e),
+        }
+    }
+}
+
+impl From<Validated<AllocateImageError>> for FractalError {
+    fn from(e: Validated<AllocateImageError>) -> Self {
+        match e {
+            Validated::Error(e) => FractalError::Allocation(Box::new(e)),
+            Validated::ValidationError(e) => FractalError::Validation(e),
+        }
+    }
+}
+
+impl From<CommandBufferExecError> for FractalError {
+    fn from(e: CommandBufferExecError) -> Self {
+        FractalError::Submit(e)
+    }
+}
+
+impl From<io::Error> for FractalError {
+    fn from(e: io::Error) -> Self {
+        FractalError::Io(e)
+    }
+}
+
+impl From<png::EncodingError> for FractalError {
+    fn from(e: png::EncodingError) -> Self {
+        FractalError::Encoding(e)
+    }
+}
@@ -0,0 +1,48 @@
+// Iteration, coloring and `main` shared by the Julia and Mandelbrot shaders. They include
+// parameters.glsl, define `orbit_start` for the first `z` and the `c` added every iteration,
+// then include this.
+#include "color.glsl"
+#include "sampling.glsl"
+#include "accumulate.glsl"
+
+vec4 sample_color(rvec2 coordinates) {
+    const rvec2 colorCenter = rvec2(0.0, 0.0);
+
+    // orbit trap coloring
+    real minDist = 1e20;
+    real tempDist = 1e20;
+
+    rcomplex z;
+    rcomplex c;
+    orbit_start(plane_point(coordinates), z, c);
+
+    const int maxIterations = p.iterations;
+
+    int i;
+    for (i = 0; i < maxIterations; i += 1) {
+        z = complex_sqr_add(z, c);
+
+        tempDist = length(complex_value(z) - colorCenter);
+        if (minDist > tempDist) {
+            minDist = tempDist;
+        }
+
+        if (length(complex_value(z)) > p.bailout) {
+            break;
+        }
+    }
+
+    const float palette_shift = p.palette_offset + float(p.time) * p.palette_speed;
+    return fractal_color(i, maxIterations, complex_value(z), minDist, p.bailout, p.coloring, palette_shift);
+}
+
+void main() {
+    // The dispatch is rounded up to whole workgroups, skip invocations outside the image
+    if (any(greaterThanEqual(gl_GlobalInvocationID.xy, uvec2(imageSize(img))))) {
+        return;
+    }
+
+    vec4 to_write = pixel_color();
+
+    imageStore(img, ivec2(gl_GlobalInvocationID.xy), to_write);
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use vulkano::device::Device;
+use vulkano::shader::ShaderModule;
+use vulkano::{Validated, VulkanError};
+
+use crate::coloring::{Coloring, DEFAULT_BAILOUT};
+use crate::cs;
+use crate::precision::Precision;
+use crate::renderer::ParameterBlock;
+use crate::view::View;
+
+/// Which fractal to iterate. Each kind has its own shader and compute pipeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FractalKind {
+    /// `z = z^2 + c` with `c` fixed (`Parameters::mouse_pos`) and `z` starting at the pixel.
+    Julia,
+    /// `z = z^2 + c` with `c` at the pixel and `z` starting at zero.
+    Mandelbrot,
+}
+
+impl FractalKind {
+    pub const ALL: [FractalKind; 2] = [FractalKind::Julia, FractalKind::Mandelbrot];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FractalKind::Julia => "julia",
+            FractalKind::Mandelbrot => "mandelbrot",
+        }
+    }
+
+    /// The kind after this one, wrapping around. Used to cycle through kinds with a key.
+    pub fn next(self) -> FractalKind {
+        let index = FractalKind::ALL.iter().position(|&k| k == self).unwrap();
+        FractalKind::ALL[(index + 1) % FractalKind::ALL.len()]
+    }
+
+    /// The shader for this kind iterating with `precision`, reading its parameters from `block`.
+    pub fn load_shader(
+        self,
+        device: Arc<Device>,
+        precision: Precision,
+        block: ParameterBlock,
+    ) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        use ParameterBlock::{PushConstants, UniformBuffer};
+
+        match (self, precision, block) {
+            (FractalKind::Julia, Precision::Float64, PushConstants) => cs::load_julia_f64(device),
+            (FractalKind::Julia, Precision::Df64, PushConstants) => cs::load_julia_df64(device),
+            (FractalKind::Julia, Precision::Float32, PushConstants) => cs::load_julia_f32(device),
+            (FractalKind::Julia, Precision::Float64, UniformBuffer) => {
+                cs::load_julia_f64_uniform(device)
+            }
+            (FractalKind::Julia, Precision::Df64, UniformBuffer) => {
+                cs::load_julia_df64_uniform(device)
+            }
+            (FractalKind::Julia, Precision::Float32, UniformBuffer) => {
+                cs::load_julia_f32_uniform(device)
+            }
+            (FractalKind::Mandelbrot, Precision::Float64, PushConstants) => {
+                cs::load_mandelbrot_f64(device)
+            }
+            (FractalKind::Mandelbrot, Precision::Df64, PushConstants) => {
+                cs::load_mandelbrot_df64(device)
+            }
+            (FractalKind::Mandelbrot, Precision::Float32, PushConstants) => {
+                cs::load_mandelbrot_f32(device)
+            }
+            (FractalKind::Mandelbrot, Precision::Float64, UniformBuffer) => {
+                cs::load_mandelbrot_f64_uniform(device)
+            }
+            (FractalKind::Mandelbrot, Precision::Df64, UniformBuffer) => {
+                cs::load_mandelbrot_df64_uniform(device)
+            }
+            (FractalKind::Mandelbrot, Precision::Float32, UniformBuffer) => {
+                cs::load_mandelbrot_f32_uniform(device)
+            }
+        }
+    }
+
+    /// A view that shows something interesting for this kind.
+    pub fn default_view(self) -> View {
+        match self {
+            FractalKind::Julia => View {
+                center: [0.0, 0.0],
+                scale: 0.5,
+                rotation: 0.0,
+            },
+            FractalKind::Mandelbrot => View {
+                center: [-0.7451544, 0.1853],
+                scale: 3.0,
+                rotation: 0.0,
+            },
+        }
+    }
+
+    /// Parameters for [`FractalKind::default_view`].
+    pub fn default_parameters(self) -> cs::Parameters {
+        let mouse_pos = match self {
+            FractalKind::Julia => [-0.162, -1.04],
+            FractalKind::Mandelbrot => [0.0, 0.0],
+        };
+
+        let mut parameters = cs::Parameters {
+            center: [0.0, 0.0],
+            axis_x: [1.0, 0.0],
+            axis_y: [0.0, 1.0],
+            mouse_pos,
+            time: 0.0,
+            bailout: DEFAULT_BAILOUT,
+            iterations: 300,
+            coloring: Coloring::Smooth as i32,
+            samples: 1,
+            sample_index: -1,
+            palette_offset: 0.0,
+            palette_speed: 0.0,
+        };
+        self.default_view().apply(&mut parameters);
+        parameters
+    }
+
+    /// A view that fits the whole set, e.g. to pick a Julia constant from the Mandelbrot set.
+    pub fn overview(self) -> View {
+        match self {
+            FractalKind::Julia => View {
+                center: [0.0, 0.0],
+                scale: 3.0,
+                rotation: 0.0,
+            },
+            FractalKind::Mandelbrot => View {
+                center: [-0.5, 0.0],
+                scale: 3.0,
+                rotation: 0.0,
+            },
+        }
+    }
+}
+
+impl fmt::Display for FractalKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for FractalKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FractalKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = FractalKind::ALL.iter().map(|k| k.name()).collect();
+                format!(
+                    "unknown fractal kind `{s}`, expected one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
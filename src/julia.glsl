@@ -0,0 +1,10 @@
+// The Julia set, included by the entry shaders for each precision.
+#include "parameters.glsl"
+
+// Every point of the plane starts its own orbit, all with the same constant
+void orbit_start(rcomplex point, out rcomplex z, out rcomplex c) {
+    z = point;
+    c = julia_constant();
+}
+
+#include "escape_time.glsl"
@@ -0,0 +1,6 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+// The Julia set in double-float precision, much faster than doubles on most GPUs
+#define DF64
+#include "julia.glsl"
@@ -0,0 +1,6 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+// The Julia set in single precision, for devices without shader_float64
+#define FLOAT32
+#include "julia.glsl"
@@ -0,0 +1,7 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+// The Julia set in single precision, for devices without shader_float64, with its parameters in a uniform buffer
+#define FLOAT32
+#define PARAMETERS_UNIFORM
+#include "julia.glsl"
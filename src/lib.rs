@@ -1,5 +1,109 @@
-/* mod cs {
+//! Fractal rendering on the GPU with a vulkano compute pipeline.
+//!
+//! [`FractalRenderer`] owns the device, queue, allocators and pipeline, so other tools can render
+//! fractals without a window:
+//!
+//! ```no_run
+//! use vulkano_fractals::{DeviceChoice, FractalError, FractalKind, FractalRenderer};
+//!
+//! let renderer = FractalRenderer::new_headless(DeviceChoice::Best)?;
+//! let kind = FractalKind::Mandelbrot;
+//! let parameters = kind.default_parameters();
+//! let pixels: Vec<u8> = renderer.render_to_rgba8(kind, &parameters, [1024, 1024])?;
+//! # Ok::<(), FractalError>(())
+//! ```
+//!
+//! Failures are reported as [`FractalError`] rather than panics.
+
+mod animation;
+mod bigfloat;
+mod bookmark;
+mod coloring;
+mod deep;
+mod device;
+mod error;
+mod fractal;
+mod palette;
+mod poster;
+mod precision;
+mod renderer;
+mod view;
+
+pub use animation::{render_frames, Animation, Easing, Keyframe};
+pub use bigfloat::BigFixed;
+pub use bookmark::Bookmark;
+pub use coloring::{Coloring, DEFAULT_BAILOUT};
+pub use deep::{precision_bits, DeepReference, DeepView};
+pub use device::{device_report, headless_instance, select_device, DeviceChoice, Queues};
+pub use error::FractalError;
+pub use fractal::FractalKind;
+pub use palette::Palette;
+pub use poster::{render_poster, TILE_EXTENT};
+pub use precision::Precision;
+pub use renderer::{
+    create_compute_pipeline, FractalRenderer, ParameterBlock, RenderTarget, WORKGROUP_SIZE,
+};
+pub use view::View;
+
+/* NOTE: `Parameters` is autogenerated by shaders!() from the GLSL struct, use that */
+/* Each kind has an entry shader per precision, see precision.glsl, and per parameter block */
+pub mod cs {
     vulkano_shaders::shader! {
-        ty: "compute",
-        path: "src/fractal.comp",
-    } */
\ No newline at end of file
+        shaders: {
+            julia_f64: {
+                ty: "compute",
+                path: "src/julia_f64.glsl",
+            },
+            julia_f32: {
+                ty: "compute",
+                path: "src/julia_f32.glsl",
+            },
+            julia_df64: {
+                ty: "compute",
+                path: "src/julia_df64.glsl",
+            },
+            julia_f64_uniform: {
+                ty: "compute",
+                path: "src/julia_f64_uniform.glsl",
+            },
+            julia_f32_uniform: {
+                ty: "compute",
+                path: "src/julia_f32_uniform.glsl",
+            },
+            julia_df64_uniform: {
+                ty: "compute",
+                path: "src/julia_df64_uniform.glsl",
+            },
+            mandelbrot_f64: {
+                ty: "compute",
+                path: "src/mandelbrot_f64.glsl",
+            },
+            mandelbrot_f32: {
+                ty: "compute",
+                path: "src/mandelbrot_f32.glsl",
+            },
+            mandelbrot_df64: {
+                ty: "compute",
+                path: "src/mandelbrot_df64.glsl",
+            },
+            mandelbrot_f64_uniform: {
+                ty: "compute",
+                path: "src/mandelbrot_f64_uniform.glsl",
+            },
+            mandelbrot_f32_uniform: {
+                ty: "compute",
+                path: "src/mandelbrot_f32_uniform.glsl",
+            },
+            mandelbrot_df64_uniform: {
+                ty: "compute",
+                path: "src/mandelbrot_df64_uniform.glsl",
+            },
+            deep: {
+                ty: "compute",
+                path: "src/deep.glsl",
+            },
+        },
+        /* `PartialEq` lets the window tell when the view changed, see progressive.rs */
+        custom_derives: [Clone, Copy, PartialEq],
+    }
+}
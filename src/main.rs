@@ -1,65 +1,58 @@
 extern crate nalgebra_glm as glm;
 
 use std::env;
+use std::io;
+use std::process;
 use std::sync::Arc;
-use std::time::SystemTime;
-
-use image::{ImageBuffer, Rgba};
-use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
-use vulkano::shader::{EntryPoint, ShaderModule};
-use vulkano::{VulkanLibrary, Version, shader, Validated, VulkanError};
-use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
-use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
-use vulkano::command_buffer::{
-    AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, BlitImageInfo,
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use vulkano::command_buffer::{AutoCommandBufferBuilder, BlitImageInfo, CommandBufferUsage};
+use vulkano::device::{Device, DeviceExtensions};
+use vulkano::image::{Image, ImageUsage};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::swapchain::{
+    acquire_next_image, PresentMode, Surface, Swapchain, SwapchainCreateInfo, SwapchainPresentInfo,
 };
-use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
-use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
-use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags, DeviceExtensions, Features, Queue};
-use vulkano::format::Format;
-use vulkano::image::view::ImageView;
-use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage};
-use vulkano::instance::{Instance, InstanceCreateInfo, InstanceCreateFlags};
-use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
-use vulkano::pipeline::compute::ComputePipelineCreateInfo;
-use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
-use vulkano::pipeline::{
-    ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo,
-};
-use vulkano::swapchain::{Surface, Swapchain, SwapchainCreateInfo, PresentMode, SwapchainPresentInfo, acquire_next_image};
+use vulkano::sync::future::FenceSignalFuture;
 use vulkano::sync::{self, GpuFuture};
+use vulkano::{Validated, VulkanError, VulkanLibrary};
 use winit::dpi::PhysicalPosition;
-use winit::event::{Event, WindowEvent};
-use winit::event_loop::{EventLoop, ControlFlow};
-use winit::window::{WindowBuilder, Window};
-
-use crate::cs::Parameters;
+use winit::event::{
+    ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
 
+use vulkano_fractals::{
+    cs, device_report, headless_instance, render_frames, render_poster, select_device, Animation,
+    Bookmark, Coloring, DeepReference, DeepView, DeviceChoice, FractalError, FractalKind,
+    FractalRenderer, Palette, Precision, RenderTarget, View,
+};
 
-// This has to be arrays actually, I don't feel like using bytemuck and glm::Vec2 for now. 
+use cli::{Mode, Options};
+use progressive::{Refinement, Scene, DOWNSCALES};
+use timing::FrameTimer;
 
-/* NOTE: This is actually autogenerated by shaders!(), use that instead */
-/*
-struct Parameters {
-    center: [f32; 2], 
-    time: f32,
-    scale: f32,
-}  */
+mod cli;
+mod progressive;
+mod timing;
 
-pub fn create_swapchain(device: Arc<Device>, surface: &Arc<Surface>, window: &Arc<Window>) -> (Arc<Swapchain>, Vec<Arc<Image>>) {
+pub fn create_swapchain(
+    device: Arc<Device>,
+    surface: &Arc<Surface>,
+    window: &Arc<Window>,
+) -> Result<(Arc<Swapchain>, Vec<Arc<Image>>), FractalError> {
     let (swapchain, images) = {
         // Querying the capabilities of the surface. When we create the swapchain we can only pass
         // values that are allowed by the capabilities.
         let surface_capabilities = device
             .physical_device()
-            .surface_capabilities(&surface, Default::default())
-            .unwrap();
+            .surface_capabilities(surface, Default::default())?;
 
         // Choosing the internal format that the images will have.
         let image_format = device
             .physical_device()
-            .surface_formats(&surface, Default::default())
-            .unwrap()[0]
+            .surface_formats(surface, Default::default())?[0]
             .0;
 
         // Please take a look at the docs for the meaning of the parameters we didn't mention.
@@ -73,7 +66,6 @@ pub fn create_swapchain(device: Arc<Device>, surface: &Arc<Surface>, window: &Ar
                 min_image_count: surface_capabilities.min_image_count.max(2),
                 //pre_transform: SurfaceTransform::HorizontalMirror,
                 present_mode: PresentMode::Fifo,
-                
 
                 image_format,
 
@@ -104,181 +96,202 @@ pub fn create_swapchain(device: Arc<Device>, surface: &Arc<Surface>, window: &Ar
 
                 ..Default::default()
             },
-        )
-        .unwrap()
+        )?
     };
 
-    (swapchain, images)
+    Ok((swapchain, images))
+}
+
+/// What one frame in flight renders into. While the GPU is still blitting a frame to the
+/// swapchain, the next one is recorded and rendered into another `Frame`'s images.
+struct Frame {
+    /// One per `progressive::DOWNSCALES`, the first one is the same size as the swapchain so the
+    /// blit is 1:1.
+    fractal_targets: Vec<RenderTarget>,
+    /// Julia preview shown in the corner while picking its constant from the Mandelbrot set.
+    inset_target: RenderTarget,
+    /// Signaled once the GPU is done with this frame, `None` before its first submission.
+    fence: Option<Arc<FenceSignalFuture<Box<dyn GpuFuture + Send + Sync>>>>,
 }
 
-mod cs {
-    vulkano_shaders::shader! {
-        ty: "compute",
-        path: "src/fractal.glsl",
+impl Frame {
+    fn new(renderer: &FractalRenderer, extent: [u32; 2]) -> Result<Self, FractalError> {
+        Ok(Frame {
+            fractal_targets: fractal_targets(renderer, extent)?,
+            inset_target: renderer.create_target([256, 256])?,
+            fence: None,
+        })
     }
 }
 
-pub fn create_compute_pipeline(device: Arc<Device>) -> Arc<ComputePipeline> {
-    
-    let shader: Arc<ShaderModule> = cs::load(device.clone()).expect("failed to create shader module");
-    
-    let entry_point: EntryPoint = shader.entry_point("main").unwrap();
-
-    let stage = PipelineShaderStageCreateInfo::new(entry_point);
-
-    let layout = PipelineLayout::new(
-        device.clone(),
-        PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
-            .into_pipeline_layout_create_info(device.clone())
-            .unwrap(),
-    )
-    .unwrap();
-
-    let compute_pipeline = ComputePipeline::new(
-        device.clone(),
-        None,
-        ComputePipelineCreateInfo::stage_layout(stage, layout),
-    )
-    .expect("failed to create compute pipeline");
-
-    compute_pipeline
+/// Targets for the full resolution passes and the coarse ones of `Refinement`.
+fn fractal_targets(
+    renderer: &FractalRenderer,
+    extent: [u32; 2],
+) -> Result<Vec<RenderTarget>, FractalError> {
+    DOWNSCALES
+        .iter()
+        .map(|&downscale| renderer.create_target(extent.map(|e| (e / downscale).max(1))))
+        .collect()
 }
 
-pub fn select_device(
-    instance: Arc<Instance>, 
-    mut device_extensions: DeviceExtensions, 
-    surface: &Arc<Surface>
-) 
-    -> (Arc<Device>, Arc<Queue>) 
-    {
-    let (physical_device, queue_family_index) = instance
-        .enumerate_physical_devices()
-        .unwrap()
-        .filter(|p| {
-            // For this example, we require at least Vulkan 1.3, or a device that has the
-            // `khr_dynamic_rendering` extension available.
-            p.api_version() >= Version::V1_3 || p.supported_extensions().khr_dynamic_rendering
-        })
-        .filter(|p| {
-            // Some devices may not support the extensions or features that your application, or
-            // report properties and limits that are not sufficient for your application. These
-            // should be filtered out here.
-            p.supported_extensions().contains(&device_extensions)
-        })
-        .filter_map(|p| {
-            // For each physical device, we try to find a suitable queue family that will execute
-            // our draw commands.
-            //
-            // Devices can provide multiple queues to run commands in parallel (for example a draw
-            // queue and a compute queue), similar to CPU threads. This is something you have to
-            // have to manage manually in Vulkan. Queues of the same type belong to the same queue
-            // family.
-            //
-            // Here, we look for a single queue family that is suitable for our purposes. In a
-            // real-world application, you may want to use a separate dedicated transfer queue to
-            // handle data transfers in parallel with graphics operations. You may also need a
-            // separate queue for compute operations, if your application uses those.
-            p.queue_family_properties()
-                .iter()
-                .enumerate()
-                .position(|(i, q)| {
-                    // We select a queue family that supports graphics operations. When drawing to
-                    // a window surface, as we do in this example, we also need to check that
-                    // queues in this queue family are capable of presenting images to the surface.
-                    q.queue_flags.intersects(QueueFlags::COMPUTE)
-                        && p.surface_support(i as u32, &surface).unwrap_or(false)
-                })
-                // The code here searches for the first queue family that is suitable. If none is
-                // found, `None` is returned to `filter_map`, which disqualifies this physical
-                // device.
-                .map(|i| (p, i as u32))
-        })
-        // All the physical devices that pass the filters above are suitable for the application.
-        // However, not every device is equal, some are preferred over others. Now, we assign each
-        // physical device a score, and pick the device with the lowest ("best") score.
-        //
-        // In this example, we simply select the best-scoring device to use in the application.
-        // In a real-world setting, you may want to use the best-scoring device only as a "default"
-        // or "recommended" device, and let the user choose the device themself.
-        .min_by_key(|(p, _)| {
-            // We assign a lower score to device types that are likely to be faster/better.
-            match p.properties().device_type {
-                PhysicalDeviceType::DiscreteGpu => 0,
-                PhysicalDeviceType::IntegratedGpu => 1,
-                PhysicalDeviceType::VirtualGpu => 2,
-                PhysicalDeviceType::Cpu => 3,
-                PhysicalDeviceType::Other => 4,
-                _ => 5,
-            }
-        })
-    .expect("no suitable physical device found");
+/// Renders a single frame of the fractal without a window, surface or swapchain, and saves it
+/// as a PNG. This is what CI and render farm machines without a display (llvmpipe/lavapipe) use.
+/// Large sizes are rendered in tiles and streamed to disk, so posters of any size fit in memory.
+fn render_headless(
+    device: DeviceChoice,
+    precision: Option<Precision>,
+    kind: FractalKind,
+    parameters: &cs::Parameters,
+    palette: &Palette,
+    extent: [u32; 2],
+    output_path: &str,
+) -> Result<(), FractalError> {
+    let mut renderer = FractalRenderer::new_headless(device)?;
+    renderer.set_palette(palette)?;
+    set_precision(&mut renderer, precision)?;
+
+    render_poster(&renderer, kind, parameters, extent, output_path)?;
+
+    println!("Saved {output_path}");
+    Ok(())
+}
 
-    if physical_device.api_version() < Version::V1_3 {
-        device_extensions.khr_dynamic_rendering = true;
+/// Switches to the precision asked for on the command line, if any, and reports the one in use.
+fn set_precision(
+    renderer: &mut FractalRenderer,
+    precision: Option<Precision>,
+) -> Result<(), FractalError> {
+    if let Some(precision) = precision {
+        renderer.set_precision(precision)?;
     }
+    println!("Precision: {}", renderer.precision());
+    Ok(())
+}
 
-    // Some little debug infos.
-    println!(
-        "Using device: {} (type: {:?})",
-        physical_device.properties().device_name,
-        physical_device.properties().device_type,
-    );
-
-    // If the selected device doesn't have Vulkan 1.3 available, then we need to enable the
-    // `khr_dynamic_rendering` extension manually. This extension became a core part of Vulkan
-    // in version 1.3 and later, so it's always available then and it does not need to be enabled.
-    // We can be sure that this extension will be available on the selected physical device,
-    // because we filtered out unsuitable devices in the device selection code above.
-    if physical_device.api_version() < Version::V1_3 {
-        device_extensions.khr_dynamic_rendering = true;
+/// Exit status for `error`, see `cli::USAGE`: 3 when there is no usable Vulkan device, 1 for
+/// everything else that goes wrong while rendering.
+fn exit_code(error: &FractalError) -> i32 {
+    match error {
+        FractalError::Loading(_)
+        | FractalError::NoDevice(_)
+        | FractalError::MissingFeature { .. } => 3,
+        _ => 1,
     }
+}
 
-    let (device, mut queues) = Device::new(
-        // Which physical device to connect to.
-        physical_device,
-        DeviceCreateInfo {
-            // The list of queues that we are going to use. Here we only use one queue, from the
-            // previously chosen queue family.
-            queue_create_infos: vec![QueueCreateInfo {
-                queue_family_index,
-                ..Default::default()
-            }],
-
-            // A list of optional features and extensions that our program needs to work correctly.
-            // Some parts of the Vulkan specs are optional and must be enabled manually at device
-            // creation. In this example the only things we are going to need are the
-            // `khr_swapchain` extension that allows us to draw to a window, and
-            // `khr_dynamic_rendering` if we don't have Vulkan 1.3 available.
-            enabled_extensions: device_extensions,
-
-            // In order to render with Vulkan 1.3's dynamic rendering, we need to enable it here.
-            // Otherwise, we are only allowed to render with a render pass object, as in the
-            // standard triangle example. The feature is required to be supported by the device if
-            // it supports Vulkan 1.3 and higher, or if the `khr_dynamic_rendering` extension is
-            // available, so we don't need to check for support.
-            enabled_features: Features {
-                shader_float64: true,
-                dynamic_rendering: true,
-                ..Features::empty()
-            },
+/// Reports `error` and exits, for the event loop which has nowhere to return errors to.
+fn fail(error: FractalError) -> ! {
+    eprintln!("error: {error}");
+    process::exit(exit_code(&error));
+}
 
-            ..Default::default()
-        },
-    )
-    .unwrap();
+trait OrExit<T> {
+    fn or_exit(self) -> T;
+}
 
-    let queue = queues.next().unwrap();
-    (device, queue)
+impl<T, E: Into<FractalError>> OrExit<T> for Result<T, E> {
+    fn or_exit(self) -> T {
+        self.unwrap_or_else(|e| fail(e.into()))
+    }
+}
+
+/// The swapchain can't be recreated or acquired from once its surface is gone.
+fn swapchain_error(error: Validated<VulkanError>) -> FractalError {
+    match error {
+        Validated::Error(e) => FractalError::SwapchainLost(e),
+        Validated::ValidationError(e) => FractalError::Validation(e),
+    }
 }
 
 fn main() {
-    println!("Hello, world!");
     env::set_var("RUST_BACKTRACE", "1");
-    
-    let event_loop = EventLoop::new();
 
+    /* See `cli::USAGE` for the arguments */
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("{e}\n\nRun with --help to see the available arguments.");
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(options) {
+        fail(e);
+    }
+}
 
-    let library = VulkanLibrary::new().unwrap();
+fn run(options: Options) -> Result<(), FractalError> {
+    let Options {
+        kind,
+        parameters: start_parameters,
+        palette,
+        animation,
+        ..
+    } = options;
+
+    match &options.mode {
+        Mode::Interactive => (),
+        Mode::ListDevices => {
+            print!("{}", device_report(&headless_instance()?)?);
+            return Ok(());
+        }
+        Mode::Headless { output } => {
+            render_headless(
+                options.device.clone(),
+                options.precision,
+                kind,
+                &start_parameters,
+                &palette,
+                options.extent,
+                output,
+            )?;
+            println!("Everything succeeded!");
+            return Ok(());
+        }
+        Mode::Frames { directory } => {
+            let mut renderer = FractalRenderer::new_headless(options.device.clone())?;
+            renderer.set_palette(&palette)?;
+            set_precision(&mut renderer, options.precision)?;
+
+            let animation = animation.unwrap_or_else(|| {
+                Animation::zoom_into(
+                    kind,
+                    View::from(&start_parameters),
+                    start_parameters.mouse_pos,
+                )
+            });
+            let frames = options
+                .frame_count
+                .unwrap_or(animation.frame_count(options.fps));
+            render_frames(
+                &renderer,
+                kind,
+                &start_parameters,
+                &animation,
+                options.fps,
+                frames,
+                options.extent,
+                directory,
+            )?;
+
+            println!("Saved {frames} frames to {directory}");
+            return Ok(());
+        }
+    }
+
+    let mut kind = kind;
+    let mut palette = palette;
+    let mut coloring = Coloring::from_i32(start_parameters.coloring).unwrap_or(Coloring::Smooth);
+    let mut bailout = start_parameters.bailout;
+    let mut palette_offset = start_parameters.palette_offset;
+    let palette_speed = start_parameters.palette_speed;
+    let mut samples = start_parameters.samples;
+
+    let event_loop = EventLoop::new();
+
+    let library = VulkanLibrary::new()?;
     /* Get extensions required by display */
     let required_extensions = Surface::required_extensions(&event_loop);
 
@@ -288,54 +301,51 @@ fn main() {
             //flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
             enabled_extensions: required_extensions,
             ..Default::default()
-        }
-    )
-    .unwrap();
+        },
+    )?;
 
     /* Set up window (winit) & surface (vulkan) */
-    let window: Arc<Window> = Arc::new(WindowBuilder::new().build(&event_loop).unwrap());
-    let surface: Arc<Surface> = Surface::from_window(instance.clone(), window.clone()).unwrap();
+    let window: Arc<Window> = Arc::new(
+        WindowBuilder::new()
+            .build(&event_loop)
+            .map_err(|e| io::Error::other(format!("failed to create a window: {e}")))?,
+    );
+    let surface: Arc<Surface> = Surface::from_window(instance.clone(), window.clone())?;
 
     let device_extensions = DeviceExtensions {
         khr_swapchain: true,
         ..DeviceExtensions::empty()
     };
 
-    let (device, queue) = select_device(instance, device_extensions, &surface);
-
-    let (mut swapchain, mut swapchain_images) = create_swapchain(device.clone(), &surface, &window);
+    let (device, queues) =
+        select_device(instance, device_extensions, Some(&surface), &options.device)?;
 
-    let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
-
-    let compute_pipeline = create_compute_pipeline(device.clone());
-
-    /* Make an image to put the fractal on */
-    // TODO: Don't we need a new image for each frame in the swapchain?
-    let fractal_image = Image::new(
-        memory_allocator.clone(),
-        ImageCreateInfo {
-            image_type: ImageType::Dim2d,
-            format: Format::R8G8B8A8_UNORM,
-            extent: [1024, 1024, 1],
-            usage: ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
-            ..Default::default()
-        },
-        AllocationCreateInfo {
-            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
-            ..Default::default()
-        },
-    )
-    .unwrap();
-
-    
-    let descriptor_set_allocator =
-        StandardDescriptorSetAllocator::new(device.clone(), Default::default());
+    let (mut swapchain, mut swapchain_images) =
+        create_swapchain(device.clone(), &surface, &window)?;
 
+    /* The fractal images are blitted on the present queue, which may be in another family */
+    let mut renderer = FractalRenderer::new(device.clone(), queues.compute.clone())?;
+    renderer.share_images_with(&queues.present);
+    if !queues.is_shared() {
+        println!("Rendering on a separate compute queue");
+    }
+    renderer.set_palette(&palette)?;
+    set_precision(&mut renderer, options.precision)?;
 
+    /* Every frame in flight has its own images to put the fractal on, so rendering the next
+     * frame doesn't overwrite one that is still being blitted. The targets keep their descriptor
+     * sets from frame to frame */
+    let mut frames = (0..options.frames_in_flight)
+        .map(|_| Frame::new(&renderer, swapchain.image_extent()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut frame_index = 0;
 
-    let command_buffer_allocator =
-        StandardCommandBufferAllocator::new(device.clone(), Default::default());
+    /* Coarse frames while the view changes, sharper ones while it stays, none once it's sharp */
+    let mut refinement = Refinement::new();
 
+    /* CPU time spent on each frame, printed once a second when switched on with T */
+    let mut frame_timer = FrameTimer::start();
+    let mut show_frame_times = false;
 
     let mut recreate_swapchain = false;
 
@@ -344,13 +354,61 @@ fn main() {
     // they are in use by the GPU.
     //
     // Destroying the `GpuFuture` blocks until the GPU is finished executing it. In order to avoid
-    // that, we store the submission of the previous frame here.
-    let mut previous_frame_end = Some(sync::now(device.clone()).boxed());
-    let start = SystemTime::now();
+    // that, every frame keeps its fence (see `Frame`), and the next submission waits on the
+    // previous frame's, which is at this index.
+    let mut previous_frame_index = 0;
+    let start = Instant::now();
 
     let mut mouse_pos: PhysicalPosition<f64> = PhysicalPosition::default();
-    
+    let mut dragging = false;
+
+    /* Keyframe animation played with Space, looping, and when it started */
+    let mut playing: Option<(Animation, Instant)> = None;
+
+    let mut view = View::from(&start_parameters);
+    let mut iterations = start_parameters.iterations;
+
+    /* Julia constant, picked by hovering over the Mandelbrot set in picking mode */
+    let mut julia_c = start_parameters.mouse_pos;
+    let mut picking = false;
+    let mut julia_c_locked = false;
+
+    /* Arbitrary precision center while deep zooming with perturbation */
+    let mut deep_view: Option<DeepView> = options.deep_view;
+
+    /* Starting in deep zoom, `view` is an offset from the deep view's center */
+    if deep_view.is_some() && !renderer.supports_deep_zoom() {
+        println!(
+            "Deep zoom needs shader_float64, showing the view in {}",
+            renderer.precision()
+        );
+        deep_view = None;
+    }
+    if deep_view.is_some() {
+        view.center = [0.0, 0.0];
+    }
+
+    println!(
+        "Tab: switch fractal, drag/scroll or arrows/+/-: pan and zoom, Q/E: rotate, R: reset view"
+    );
+    println!("Space: play animation");
+    println!(
+        "J: pick the Julia constant from the Mandelbrot set (hover to preview, right click to lock)"
+    );
+    println!("P: deep zoom with perturbation, past the precision of doubles");
+    println!(
+        "C: switch between smooth and banded coloring, G: next built-in palette, A: anti-aliasing"
+    );
+    println!("F: switch between float64, df64 and float32 precision");
+    println!("B: save the current view as a bookmark, drop a bookmark or rendered PNG to open it");
+    println!("T: show frame rate and CPU time per frame");
+
     event_loop.run(move |event, _, control_flow| {
+        let window_size = [
+            window.inner_size().width as f64,
+            window.inner_size().height as f64,
+        ];
+
         match event {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
@@ -364,14 +422,248 @@ fn main() {
             } => {
                 recreate_swapchain = true;
             }
+            Event::RedrawRequested(_) => {
+                /* The window system lost what was shown, so render it again */
+                refinement.restart();
+            }
             Event::WindowEvent {
-                event: WindowEvent::CursorMoved { device_id, position, modifiers },
+                event: WindowEvent::DroppedFile(path),
                 ..
             } => {
+                /* Dropping a bookmark or a rendered PNG restores its view */
+                let loaded = Bookmark::load(&path).and_then(|bookmark| {
+                    Ok((bookmark.palette()?, bookmark.deep_view()?, bookmark))
+                });
+                let (new_palette, new_deep_view, bookmark) = match loaded {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        println!("{e}");
+                        return;
+                    }
+                };
+
+                /* A palette the device can't take leaves everything as it was */
+                match renderer.set_palette(&new_palette) {
+                    Ok(()) => palette = new_palette,
+                    Err(e @ FractalError::InvalidPalette(_)) => {
+                        println!("{e}");
+                        return;
+                    }
+                    Err(e) => fail(e),
+                }
+
+                kind = bookmark.kind;
+                coloring = bookmark.coloring;
+                bailout = bookmark.bailout;
+                palette_offset = bookmark.palette_offset;
+                iterations = bookmark.iterations;
+                julia_c = bookmark.julia_c;
+                picking = false;
+                view = new_deep_view.to_view();
+                deep_view = None;
+                if bookmark.deep && !renderer.supports_deep_zoom() {
+                    println!(
+                        "Deep zoom needs shader_float64, showing the view in {}",
+                        renderer.precision()
+                    );
+                } else if bookmark.deep {
+                    deep_view = Some(new_deep_view);
+                    view.center = [0.0, 0.0];
+                }
+
+                println!("Opened {}", path.display());
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                if dragging {
+                    view.pan_pixels(
+                        [position.x - mouse_pos.x, position.y - mouse_pos.y],
+                        window_size,
+                    );
+                }
                 mouse_pos = position;
+
+                if picking && !julia_c_locked {
+                    julia_c = view.pixel_to_plane([position.x, position.y], window_size);
+                }
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => {
+                dragging = state == ElementState::Pressed;
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Right,
+                        ..
+                    },
+                ..
+            } if picking => {
+                julia_c = view.pixel_to_plane([mouse_pos.x, mouse_pos.y], window_size);
+                julia_c_locked = !julia_c_locked;
+                println!("Julia constant: {} + {}i", julia_c[0], julia_c[1]);
+            }
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => {
+                /* Scrolling up zooms in, around whatever is under the cursor */
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y as f64,
+                    MouseScrollDelta::PixelDelta(position) => position.y / 50.0,
+                };
+                view.zoom_at(0.9_f64.powf(lines), [mouse_pos.x, mouse_pos.y], window_size);
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let window_center = [window_size[0] / 2.0, window_size[1] / 2.0];
+                let step = [window_size[0] / 10.0, window_size[1] / 10.0];
+
+                match key {
+                    VirtualKeyCode::Tab if !picking => {
+                        deep_view = None;
+                        kind = kind.next();
+                        view = kind.default_view();
+                        iterations = kind.default_parameters().iterations;
+                        println!("Showing {kind}");
+                    }
+                    VirtualKeyCode::Left => view.pan_pixels([step[0], 0.0], window_size),
+                    VirtualKeyCode::Right => view.pan_pixels([-step[0], 0.0], window_size),
+                    VirtualKeyCode::Up => view.pan_pixels([0.0, step[1]], window_size),
+                    VirtualKeyCode::Down => view.pan_pixels([0.0, -step[1]], window_size),
+                    VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                        view.zoom_at(0.5, window_center, window_size)
+                    }
+                    VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                        view.zoom_at(2.0, window_center, window_size)
+                    }
+                    VirtualKeyCode::Q => view.rotation += std::f64::consts::PI / 12.0,
+                    VirtualKeyCode::E => view.rotation -= std::f64::consts::PI / 12.0,
+                    VirtualKeyCode::R => {
+                        deep_view = None;
+                        view = kind.default_view();
+                        iterations = kind.default_parameters().iterations;
+                    }
+                    VirtualKeyCode::Space => {
+                        /* Without `--animation`, zoom from the whole set into the current view */
+                        playing = match playing {
+                            Some(_) => None,
+                            None => {
+                                let animation = animation
+                                    .clone()
+                                    .unwrap_or_else(|| Animation::zoom_into(kind, view, julia_c));
+                                Some((animation, Instant::now()))
+                            }
+                        };
+                    }
+                    VirtualKeyCode::C => {
+                        coloring = coloring.next();
+                        println!("Coloring: {coloring}");
+                    }
+                    VirtualKeyCode::B => {
+                        let parameters = cs::Parameters {
+                            bailout,
+                            coloring: coloring as i32,
+                            iterations,
+                            palette_offset,
+                            mouse_pos: julia_c,
+                            ..kind.default_parameters()
+                        };
+                        let bookmark =
+                            Bookmark::new(kind, &view, deep_view.as_ref(), &parameters, &palette);
+
+                        /* A clock set before 1970 just gets bookmark-0.json */
+                        let seconds = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs();
+                        let path = format!("bookmark-{seconds}.json");
+                        match bookmark.save(&path) {
+                            Ok(()) => println!("Saved {path}"),
+                            Err(e) => println!("failed to save {path}: {e}"),
+                        }
+                    }
+                    VirtualKeyCode::A => {
+                        samples = samples % 4 + 1;
+                        println!("Samples per pixel: {samples}x{samples}");
+                    }
+                    VirtualKeyCode::F => {
+                        let precision = renderer.precision().next_supported(&device);
+                        renderer.set_precision(precision).or_exit();
+                        println!("Precision: {precision}");
+                    }
+                    VirtualKeyCode::T => {
+                        show_frame_times = !show_frame_times;
+                    }
+                    VirtualKeyCode::G => {
+                        palette = palette.next_built_in();
+                        renderer.set_palette(&palette).or_exit();
+                        println!("Palette: {}", palette.name);
+                    }
+                    VirtualKeyCode::J => {
+                        /* Leaving picking mode shows the Julia set for the picked constant */
+                        deep_view = None;
+                        picking = !picking;
+                        julia_c_locked = false;
+                        kind = if picking {
+                            FractalKind::Mandelbrot
+                        } else {
+                            FractalKind::Julia
+                        };
+                        view = if picking {
+                            FractalKind::Mandelbrot.overview()
+                        } else {
+                            kind.default_view()
+                        };
+                        println!("Julia constant: {} + {}i", julia_c[0], julia_c[1]);
+                    }
+                    VirtualKeyCode::P if !picking && !renderer.supports_deep_zoom() => {
+                        println!("Deep zoom needs shader_float64, which this device doesn't have");
+                    }
+                    VirtualKeyCode::P if !picking => match deep_view.take() {
+                        /* From now on `view.center` is an offset from the deep view's center */
+                        None => match DeepView::from_view(&view) {
+                            Some(deep) => {
+                                deep_view = Some(deep);
+                                view.center = [0.0, 0.0];
+                                println!("Deep zoom on");
+                            }
+                            None => println!("Can't zoom in from a center that isn't a number"),
+                        },
+                        Some(mut deep) => {
+                            deep.recenter(&mut view);
+                            println!(
+                                "Deep zoom off at {} + {}i, scale {:e}",
+                                deep.center[0], deep.center[1], deep.scale
+                            );
+                            view = deep.to_view();
+                        }
+                    },
+                    _ => (),
+                }
             }
             Event::RedrawEventsCleared => {
-                
                 // Do not draw the frame when the screen size is zero. On Windows, this can
                 // occur when minimizing the application.
                 let image_extent: [u32; 2] = window.inner_size().into();
@@ -383,8 +675,13 @@ fn main() {
                 // It is important to call this function from time to time, otherwise resources
                 // will keep accumulating and you will eventually reach an out of memory error.
                 // Calling this function polls various fences in order to determine what the GPU
-                // has already processed, and frees the resources that are no longer needed.
-                previous_frame_end.as_mut().unwrap().cleanup_finished();
+                // has already processed, and frees the resources that are no longer needed. A
+                // frame's fence is only waited for when the frame is used again, so poll them all.
+                for frame in &mut frames {
+                    if let Some(fence) = frame.fence.as_mut() {
+                        fence.cleanup_finished();
+                    }
+                }
 
                 // Whenever the window resizes we need to recreate everything dependent on the
                 // window size. In this example that includes the swapchain, the framebuffers and
@@ -395,19 +692,85 @@ fn main() {
                             image_extent,
                             ..swapchain.create_info()
                         })
-                        .expect("failed to recreate swapchain");
+                        .map_err(swapchain_error)
+                        .or_exit();
 
                     swapchain = new_swapchain;
 
                     // Now that we have new swapchain images, we must create new image views from
                     // them as well.
 
-                    // How about no... 
+                    // How about no...
                     swapchain_images = new_images;
 
+                    /* The fractal is rendered at the new size too, instead of being stretched.
+                     * Frames still in flight keep their old images alive until their fence. The
+                     * new targets start out empty, even when the size stayed the same, so there
+                     * is nothing to accumulate samples onto */
+                    for frame in &mut frames {
+                        frame.fractal_targets =
+                            fractal_targets(&renderer, swapchain.image_extent()).or_exit();
+                    }
+                    refinement.restart();
+
                     recreate_swapchain = false;
                 }
 
+                /* Deep zoom moves the offset into its own center first, so the scene stays the same
+                 * from frame to frame while nothing moves */
+                if let Some(deep) = deep_view.as_mut() {
+                    deep.recenter(&mut view);
+                }
+
+                let seconds = start.elapsed().as_secs_f64();
+
+                let mut parameters = cs::Parameters {
+                    bailout,
+                    coloring: coloring as i32,
+                    samples,
+                    palette_offset,
+                    palette_speed,
+                    time: seconds,
+                    iterations,
+                    ..kind.default_parameters()
+                };
+                view.apply(&mut parameters);
+
+                if kind == FractalKind::Julia {
+                    parameters.mouse_pos = julia_c;
+                }
+
+                if let Some((animation, started)) = &playing {
+                    let elapsed = started.elapsed().as_secs_f64();
+                    let time = if animation.duration() > 0.0 {
+                        animation.start() + elapsed % animation.duration()
+                    } else {
+                        animation.start()
+                    };
+                    animation.sample(time).apply(&mut parameters);
+                }
+
+                let scene = Scene {
+                    kind,
+                    parameters: cs::Parameters {
+                        time: 0.0,
+                        ..parameters
+                    },
+                    deep_view: deep_view.clone(),
+                    precision: renderer.precision(),
+                    palette: palette.clone(),
+                    inset: picking.then_some(julia_c),
+                    extent: swapchain.image_extent(),
+                };
+                let continuous = playing.is_some() || palette_speed != 0.0;
+
+                /* Nothing to draw until something changes, so sleep until the next event */
+                let Some(pass) = refinement.next_pass(&scene, continuous) else {
+                    *control_flow = ControlFlow::Wait;
+                    return;
+                };
+                *control_flow = ControlFlow::Poll;
+
                 // Before we can draw on the output, we have to *acquire* an image from the
                 // swapchain. If no image is available (which happens if you submit draw commands
                 // too quickly), then the function will block. This operation returns the index of
@@ -420,9 +783,10 @@ fn main() {
                         Ok(r) => r,
                         Err(VulkanError::OutOfDate) => {
                             recreate_swapchain = true;
+                            refinement.restart();
                             return;
                         }
-                        Err(e) => panic!("failed to acquire next image: {e}"),
+                        Err(e) => fail(FractalError::SwapchainLost(e)),
                     };
 
                 // `acquire_next_image` can be successful, but suboptimal. This means that the
@@ -432,99 +796,27 @@ fn main() {
                 if suboptimal {
                     recreate_swapchain = true;
                 }
-                
-                let zoomtime = 70.0;
-
-                let mut time: f64 = SystemTime::now().duration_since(start)
-                    .unwrap().as_secs_f64() / 20.0;
-                
-                
-                //let scale = 1.0 / ((((time as f64 * (time as f64 * 0.001)) as i128 % 1000000000) / 10000 )) as f32;
-
-                let mut zoom = 0.7 + 0.38 * (1.2 * time.cos());
-
-                let iterations = 100 + ((12.0 / zoom.powi(2)) as u32) % 800;
-
-                zoom = zoom.powi(8);
-
-                
-                let mut x_pos = mouse_pos.x as f64 / window.inner_size().width as f64;
-                let mut y_pos = mouse_pos.y as f64 / window.inner_size().height as f64;
-
-                x_pos = (x_pos - 0.5) * 3.0;
-                y_pos = (y_pos - 0.5) * 3.0;
-
-                //println!("x: {}, y: {}", mouse_pos.x, mouse_pos.y);
-
-
-                /* Parameters Buffer */
-                /* Create a storage buffer (What are push constants, should we use those instead? ) */
-
-                /* Julia Set: */
-                x_pos = -0.162;
-                y_pos = -1.04;
 
-                let parameters = cs::Parameters {
-                    center: [0.0, 0.0], //[-0.7451544, 0.1853],
-                    time: 0.0,
-                    scale: 0.5, // zoom as f64, // time * 100.0,
-                    mouse_pos: [x_pos, y_pos],
-                    iterations: 300 as i32, 
-                    // ((iterations % 10000) / 100 ) as i32,
-                };
-
-                //println!("xpos: {x_pos} ypos:{y_pos}");
-                /* Mandelbrot */
-                
-                let _parameters = cs::Parameters {
-                    center: [-0.7451544, 0.1853],
-                    time: 0.0,
-                    scale: zoom as f64, 
-                    mouse_pos: [0.0, 0.0],
-                    iterations: iterations as i32, 
-                    // ((iterations % 10000) / 100 ) as i32,
-                };  
-
-                // TODO: Reuuse buffer, or make it a staging buffer
-                let parameters_buffer = Buffer::from_data(
-                    memory_allocator.clone(),
-                    BufferCreateInfo {
-                        usage: BufferUsage::STORAGE_BUFFER,
-                        ..Default::default()
-                    },
-                    AllocationCreateInfo {
-                        memory_type_filter: 
-                        MemoryTypeFilter::PREFER_DEVICE |
-                        MemoryTypeFilter::HOST_RANDOM_ACCESS,
-                        ..Default::default()
-                    },
-                    parameters
-                )
-                .expect("failed to create buffer");
-
-               
-                
-
-                /* Attach to compute pipeline */
-                let view = ImageView::new_default(fractal_image.clone()).unwrap();
-
-                let count = compute_pipeline.layout().set_layouts().len();
+                /* Accumulating samples adds to the sums of the previous pass, so it renders into
+                 * the same frame. The submission is chained after that pass (see below) rather
+                 * than waiting for it here, and `cleanup_finished` above frees the chain as the
+                 * GPU gets through it. `--samples` caps it at 256 passes */
+                if pass.accumulates() {
+                    frame_index = previous_frame_index;
+                }
 
-                //println!("count: {count}");
+                /* Otherwise wait until the GPU is done with the last submission that used this
+                 * frame's images and parameters, which is usually long finished */
+                let frame = &mut frames[frame_index];
+                if !pass.accumulates() {
+                    if let Some(fence) = frame.fence.take() {
+                        fence.wait(None).or_exit();
+                    }
+                }
 
-                let layout = compute_pipeline.layout().set_layouts().get(0).unwrap();
-                    
-                let set: Arc<PersistentDescriptorSet> = PersistentDescriptorSet::new(
-                    &descriptor_set_allocator,
-                    layout.clone(),
-                    [WriteDescriptorSet::image_view(0, view), 
-                    WriteDescriptorSet::buffer(1, parameters_buffer)
-                    ], // 0 is the binding
-                    [],
-                )
-                .expect("Invalid descriptor set");
+                /* Acquiring waits for the display, so the CPU time starts here */
+                let frame_start = Instant::now();
 
-                    
                 // In order to draw, we have to build a *command buffer*. The command buffer object
                 // holds the list of commands that are going to be executed.
                 //
@@ -535,40 +827,139 @@ fn main() {
                 // Note that we have to pass a queue family when we create the command buffer. The
                 // command buffer will only be executable on that given queue family.
                 let mut builder = AutoCommandBufferBuilder::primary(
-                    &command_buffer_allocator,
-                    queue.queue_family_index(),
+                    renderer.command_buffer_allocator(),
+                    queues.present.queue_family_index(),
                     CommandBufferUsage::OneTimeSubmit,
                 )
-                .unwrap();
-                
-                // TODO: Make this use a compute queue, not a graphics queue. 
-                builder
-                    .bind_pipeline_compute(compute_pipeline.clone())
-                    .unwrap()
-                    .bind_descriptor_sets(
-                        PipelineBindPoint::Compute,
-                        compute_pipeline.layout().clone(),
-                        0,
-                        set,
-                    )
-                    .unwrap()
-                    .dispatch([1024 / 16, 1024 / 16, 1])
-                    .unwrap()
-                    .blit_image(
-                        BlitImageInfo::images(fractal_image.clone(), swapchain_images[image_index as usize].clone())
+                .or_exit();
+
+                /* With a separate compute queue the dispatches get their own command buffer,
+                 * otherwise they go before the blits in the present queue's */
+                let mut compute_builder = (!queues.is_shared()).then(|| {
+                    AutoCommandBufferBuilder::primary(
+                        renderer.command_buffer_allocator(),
+                        queues.compute.queue_family_index(),
+                        CommandBufferUsage::OneTimeSubmit,
                     )
-                    .unwrap();
-                    
+                    .or_exit()
+                });
+                let render_builder = compute_builder.as_mut().unwrap_or(&mut builder);
+
+                /* The Julia inset, a square a third of the short side in the bottom right corner */
+                let [width, height, _] = swapchain_images[image_index as usize].extent();
+                let side = width.min(height) / 3;
+                let show_inset = picking && side > 0;
+
+                let fractal_target = &mut frame.fractal_targets[pass.level];
+                if let Some(deep) = deep_view.as_ref() {
+                    // Deeper zooms need more iterations before the detail shows up
+                    let mut deep_parameters = cs::Parameters {
+                        iterations: parameters
+                            .iterations
+                            .max((-deep.scale.log10() * 100.0) as i32),
+                        ..parameters
+                    };
+                    pass.apply(&mut deep_parameters);
+
+                    let [width, height, _] = fractal_target.image().extent();
+                    let reference =
+                        DeepReference::new(kind, deep, &deep_parameters, [width, height]).or_exit();
+                    renderer
+                        .record_deep_dispatch(
+                            render_builder,
+                            fractal_target.image().clone(),
+                            &reference,
+                        )
+                        .or_exit();
+                } else {
+                    let mut pass_parameters = parameters;
+                    pass.apply(&mut pass_parameters);
+                    renderer
+                        .record_target_dispatch(
+                            render_builder,
+                            fractal_target,
+                            kind,
+                            &pass_parameters,
+                        )
+                        .or_exit();
+                }
+
+                if show_inset {
+                    let mut inset_parameters = cs::Parameters {
+                        bailout,
+                        coloring: coloring as i32,
+                        palette_offset,
+                        palette_speed,
+                        time: seconds,
+                        ..FractalKind::Julia.default_parameters()
+                    };
+                    FractalKind::Julia.overview().apply(&mut inset_parameters);
+                    inset_parameters.mouse_pos = julia_c;
+
+                    renderer
+                        .record_target_dispatch(
+                            render_builder,
+                            &mut frame.inset_target,
+                            FractalKind::Julia,
+                            &inset_parameters,
+                        )
+                        .or_exit();
+                }
+
+                builder
+                    .blit_image(BlitImageInfo::images(
+                        frame.fractal_targets[pass.level].image().clone(),
+                        swapchain_images[image_index as usize].clone(),
+                    ))
+                    .or_exit();
+
+                if show_inset {
+                    let mut inset_blit = BlitImageInfo::images(
+                        frame.inset_target.image().clone(),
+                        swapchain_images[image_index as usize].clone(),
+                    );
+                    inset_blit.regions[0].dst_offsets =
+                        [[width - side, height - side, 0], [width, height, 1]];
+
+                    builder.blit_image(inset_blit).or_exit();
+                }
 
                 // Finish building the command buffer by calling `build`.
-                let command_buffer = builder.build().unwrap();
+                let command_buffer = builder.build().or_exit();
+
+                /* The compute queue signals a semaphore the present queue waits for before the
+                 * blits. It only waits for the previous frame when accumulating into its images,
+                 * through a semaphore since that frame ended on the present queue. A fence that
+                 * was waited for has no queue left, and nothing to wait for */
+                let rendered = match compute_builder {
+                    Some(compute_builder) => {
+                        let previous_pass = match frames[previous_frame_index].fence.clone() {
+                            Some(fence) if pass.accumulates() && fence.queue().is_some() => {
+                                fence.then_signal_semaphore().boxed_send_sync()
+                            }
+                            _ => sync::now(device.clone()).boxed_send_sync(),
+                        };
+                        previous_pass
+                            .then_execute(queues.compute.clone(), compute_builder.build().or_exit())
+                            .or_exit()
+                            .then_signal_semaphore()
+                            .boxed_send_sync()
+                    }
+                    None => sync::now(device.clone()).boxed_send_sync(),
+                };
+
+                /* Chaining onto the previous frame keeps the presents in order, and on a shared
+                 * queue puts accumulating dispatches after the pass they add to */
+                let previous_frame_end = match frames[previous_frame_index].fence.clone() {
+                    Some(fence) => fence.boxed_send_sync(),
+                    None => sync::now(device.clone()).boxed_send_sync(),
+                };
 
                 let future = previous_frame_end
-                    .take()
-                    .unwrap()
+                    .join(rendered)
                     .join(acquire_future)
-                    .then_execute(queue.clone(), command_buffer)
-                    .unwrap()
+                    .then_execute(queues.present.clone(), command_buffer)
+                    .or_exit()
                     // The color output is now expected to contain our triangle. But in order to
                     // show it on the screen, we have to *present* the image by calling
                     // `then_swapchain_present`.
@@ -578,34 +969,35 @@ fn main() {
                     // only be presented once the GPU has finished executing the command buffer
                     // that draws the triangle.
                     .then_swapchain_present(
-                        queue.clone(),
+                        queues.present.clone(),
                         SwapchainPresentInfo::swapchain_image_index(swapchain.clone(), image_index),
                     )
+                    .boxed_send_sync()
                     .then_signal_fence_and_flush();
 
-                match future.map_err(Validated::unwrap) {
-                    Ok(future) => {
-                        previous_frame_end = Some(future.boxed());
-                    }
+                frames[frame_index].fence = match future.map_err(Validated::unwrap) {
+                    Ok(future) => Some(Arc::new(future)),
                     Err(VulkanError::OutOfDate) => {
                         recreate_swapchain = true;
-                        previous_frame_end = Some(sync::now(device.clone()).boxed());
+                        refinement.restart();
+                        None
                     }
                     Err(e) => {
                         println!("failed to flush future: {e}");
-                        previous_frame_end = Some(sync::now(device.clone()).boxed());
+                        refinement.restart();
+                        None
+                    }
+                };
+                previous_frame_index = frame_index;
+                frame_index = (frame_index + 1) % frames.len();
+
+                if let Some(report) = frame_timer.frame(frame_start.elapsed()) {
+                    if show_frame_times {
+                        println!("{report}");
                     }
                 }
             }
-        _ => (),
+            _ => (),
         }
     });
-
-
-    //let buffer_content = buf.read().unwrap();
-    //let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
-    //image.save("image.png").unwrap();
-
-    println!("Everything succeeded!");
-
 }
@@ -0,0 +1,10 @@
+// The Mandelbrot set, included by the entry shaders for each precision.
+#include "parameters.glsl"
+
+// Every orbit starts at zero, with the point of the plane as its constant
+void orbit_start(rcomplex point, out rcomplex z, out rcomplex c) {
+    z = rcomplex(0.0);
+    c = point;
+}
+
+#include "escape_time.glsl"
@@ -0,0 +1,6 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+// The Mandelbrot set in double-float precision, much faster than doubles on most GPUs
+#define DF64
+#include "mandelbrot.glsl"
@@ -0,0 +1,7 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+// The Mandelbrot set in double-float precision, much faster than doubles on most GPUs, with its parameters in a uniform buffer
+#define DF64
+#define PARAMETERS_UNIFORM
+#include "mandelbrot.glsl"
@@ -0,0 +1,6 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+// The Mandelbrot set in single precision, for devices without shader_float64
+#define FLOAT32
+#include "mandelbrot.glsl"
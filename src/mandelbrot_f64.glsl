@@ -0,0 +1,5 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+// The Mandelbrot set in double precision, needs shader_float64
+#include "mandelbrot.glsl"
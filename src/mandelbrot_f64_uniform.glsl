@@ -0,0 +1,6 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+// The Mandelbrot set in double precision, needs shader_float64, with its parameters in a uniform buffer
+#define PARAMETERS_UNIFORM
+#include "mandelbrot.glsl"
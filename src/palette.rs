@@ -0,0 +1,392 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::FractalError;
+
+/// Number of entries gradients are sampled to before being uploaded.
+const GRADIENT_RESOLUTION: usize = 256;
+
+/// A cyclic color gradient, uploaded to the shaders as a 1D texture.
+///
+/// The shaders sample it with linear filtering and repeat addressing at
+/// `hue + palette_offset + time * palette_speed`, so palettes should wrap around smoothly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    /// The built-in name, or the path of the file it was loaded from.
+    pub name: String,
+    pub colors: Vec<[u8; 4]>,
+}
+
+/// (name, evenly spaced colors) of the palettes that are always available.
+const BUILT_IN: [(&str, &[[u8; 3]]); 5] = [
+    (
+        "rainbow",
+        &[
+            [255, 0, 0],
+            [255, 255, 0],
+            [0, 255, 0],
+            [0, 255, 255],
+            [0, 0, 255],
+            [255, 0, 255],
+        ],
+    ),
+    (
+        "classic",
+        &[
+            [0, 7, 100],
+            [32, 107, 203],
+            [237, 255, 255],
+            [255, 170, 0],
+            [0, 2, 0],
+        ],
+    ),
+    (
+        "fire",
+        &[
+            [0, 0, 0],
+            [128, 0, 0],
+            [255, 96, 0],
+            [255, 224, 64],
+            [255, 255, 255],
+            [128, 32, 0],
+        ],
+    ),
+    (
+        "ocean",
+        &[
+            [0, 8, 32],
+            [0, 64, 160],
+            [0, 192, 224],
+            [224, 255, 255],
+            [0, 96, 128],
+        ],
+    ),
+    ("grayscale", &[[0, 0, 0], [255, 255, 255]]),
+];
+
+/// `{"colors": ["#000764", ...], "positions": [0.0, ...]}`, positions are optional.
+#[derive(Deserialize)]
+struct PaletteFile {
+    colors: Vec<String>,
+    #[serde(default)]
+    positions: Option<Vec<f32>>,
+}
+
+impl Palette {
+    pub fn built_in_names() -> impl Iterator<Item = &'static str> {
+        BUILT_IN.iter().map(|(name, _)| *name)
+    }
+
+    pub fn built_in(name: &str) -> Option<Palette> {
+        let (name, colors) = BUILT_IN
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))?;
+        Some(Palette::gradient(name, evenly_spaced(colors)))
+    }
+
+    /// The built-in palette after this one (by name), wrapping around. Used to cycle with a key.
+    pub fn next_built_in(&self) -> Palette {
+        let index = BUILT_IN.iter().position(|(n, _)| *n == self.name);
+        let next = index.map_or(0, |i| (i + 1) % BUILT_IN.len());
+        Palette::gradient(BUILT_IN[next].0, evenly_spaced(BUILT_IN[next].1))
+    }
+
+    /// A built-in palette by name, otherwise a gradient file (see [`Palette::load`]).
+    pub fn from_name_or_path(name_or_path: &str) -> Result<Palette, FractalError> {
+        match Palette::built_in(name_or_path) {
+            Some(palette) => Ok(palette),
+            None => Palette::load(name_or_path),
+        }
+    }
+
+    /// Evenly spaced colors, wrapping from the last back to the first. Fails with
+    /// [`FractalError::InvalidPalette`] without colors.
+    pub fn from_colors(name: &str, colors: &[[u8; 3]]) -> Result<Palette, FractalError> {
+        Palette::from_stops(name, &evenly_spaced(colors))
+    }
+
+    /// Linear gradient through `(position, color)` stops with positions in `[0, 1)`, wrapping
+    /// from the last stop back to the first. Fails with [`FractalError::InvalidPalette`] without
+    /// stops or with a position outside `[0, 1)`.
+    pub fn from_stops(name: &str, stops: &[(f32, [u8; 3])]) -> Result<Palette, FractalError> {
+        check_stops(stops).map_err(|e| FractalError::InvalidPalette(format!("{name}: {e}")))?;
+        Ok(Palette::gradient(name, stops.to_vec()))
+    }
+
+    /// See [`Palette::from_stops`], `stops` are already checked by [`check_stops`].
+    fn gradient(name: &str, mut stops: Vec<(f32, [u8; 3])>) -> Palette {
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // The segment past the last stop runs into the first one, one period later
+        let first = stops[0];
+        stops.push((first.0 + 1.0, first.1));
+
+        let colors = (0..GRADIENT_RESOLUTION)
+            .map(|i| {
+                let mut t = i as f32 / GRADIENT_RESOLUTION as f32;
+                if t < first.0 {
+                    t += 1.0;
+                }
+
+                let end = stops
+                    .iter()
+                    .position(|stop| stop.0 > t)
+                    .unwrap_or(stops.len() - 1);
+                let (start, end) = (stops[end.saturating_sub(1)], stops[end]);
+                let f = if end.0 > start.0 {
+                    (t - start.0) / (end.0 - start.0)
+                } else {
+                    0.0
+                };
+
+                let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * f).round() as u8;
+                [
+                    mix(start.1[0], end.1[0]),
+                    mix(start.1[1], end.1[1]),
+                    mix(start.1[2], end.1[2]),
+                    255,
+                ]
+            })
+            .collect();
+
+        Palette {
+            name: name.to_string(),
+            colors,
+        }
+    }
+
+    /// Fails with [`FractalError::InvalidPalette`] unless there is at least one color and at most
+    /// `max_width`, the widest 1D image a device can sample.
+    pub(crate) fn check_width(&self, max_width: u32) -> Result<(), FractalError> {
+        if self.colors.is_empty() {
+            return Err(FractalError::InvalidPalette(format!(
+                "{} has no colors",
+                self.name
+            )));
+        }
+        if self.colors.len() > max_width as usize {
+            return Err(FractalError::InvalidPalette(format!(
+                "{} has {} colors, this device's 1D images are at most {max_width} wide",
+                self.name,
+                self.colors.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Loads a gradient file, picked by extension:
+    ///
+    /// - `.png` (or any other image): a strip, the middle row is used as is.
+    /// - `.json`: `{"colors": ["#rrggbb", ...], "positions": [0.0, ...]}`.
+    /// - anything else: text with one color per line, `#rrggbb` or `r g b`, optionally preceded by
+    ///   its position in `[0, 1)`. Lines starting with `//` are comments.
+    ///
+    /// Fails with [`FractalError::InvalidPalette`], also when the file can't be read.
+    pub fn load(path: impl AsRef<Path>) -> Result<Palette, FractalError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let error =
+            |e: &dyn std::fmt::Display| FractalError::InvalidPalette(format!("{name}: {e}"));
+
+        if image::ImageFormat::from_path(path).is_ok() {
+            let strip = image::open(path).map_err(|e| error(&e))?.to_rgba8();
+            let row = strip.height() / 2;
+            let colors = (0..strip.width())
+                .map(|x| strip.get_pixel(x, row).0)
+                .collect();
+            return Ok(Palette { name, colors });
+        }
+
+        let text = fs::read_to_string(path).map_err(|e| error(&e))?;
+        let stops = if extension == "json" {
+            parse_json(&text)
+        } else {
+            parse_text(&text)
+        }
+        .map_err(|e| error(&e))?;
+        Ok(Palette::gradient(&name, stops))
+    }
+}
+
+/// Fails without stops or with a position outside `[0, 1)`, see [`Palette::from_stops`].
+fn check_stops(stops: &[(f32, [u8; 3])]) -> Result<(), String> {
+    if stops.is_empty() {
+        return Err("no colors".to_string());
+    }
+    if let Some((position, _)) = stops.iter().find(|(p, _)| !(0.0..1.0).contains(p)) {
+        return Err(format!("position {position} is outside of [0, 1)"));
+    }
+    Ok(())
+}
+
+/// Checked stops of a gradient file, evenly spaced when it has no positions.
+fn stops(positions: Option<Vec<f32>>, colors: Vec<[u8; 3]>) -> Result<Vec<(f32, [u8; 3])>, String> {
+    let stops = match positions {
+        None => evenly_spaced(&colors),
+        Some(positions) if positions.len() == colors.len() => {
+            positions.into_iter().zip(colors).collect()
+        }
+        Some(_) => return Err("either every color or none needs a position".to_string()),
+    };
+    check_stops(&stops)?;
+    Ok(stops)
+}
+
+/// See [`Palette::load`] for the format.
+fn parse_json(text: &str) -> Result<Vec<(f32, [u8; 3])>, String> {
+    let file: PaletteFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let colors = file
+        .colors
+        .iter()
+        .map(|c| parse_hex(c))
+        .collect::<Result<Vec<_>, _>>()?;
+    stops(file.positions, colors)
+}
+
+/// `colors` spread over `[0, 1)`, for [`Palette::gradient`].
+fn evenly_spaced(colors: &[[u8; 3]]) -> Vec<(f32, [u8; 3])> {
+    let step = 1.0 / colors.len() as f32;
+    colors
+        .iter()
+        .enumerate()
+        .map(|(i, color)| (i as f32 * step, *color))
+        .collect()
+}
+
+/// See [`Palette::load`] for the format.
+fn parse_text(text: &str) -> Result<Vec<(f32, [u8; 3])>, String> {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (position, color) = match tokens.as_slice() {
+            [hex] => (None, parse_hex(hex)?),
+            [position, hex] => (Some(*position), parse_hex(hex)?),
+            [r, g, b] => (None, parse_rgb(r, g, b)?),
+            [position, r, g, b] => (Some(*position), parse_rgb(r, g, b)?),
+            _ => return Err(format!("cannot parse palette line `{line}`")),
+        };
+
+        if let Some(position) = position {
+            positions.push(
+                position
+                    .parse::<f32>()
+                    .map_err(|_| format!("invalid position `{position}`"))?,
+            );
+        }
+        colors.push(color);
+    }
+
+    stops((!positions.is_empty()).then_some(positions), colors)
+}
+
+fn parse_hex(hex: &str) -> Result<[u8; 3], String> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    let value = u32::from_str_radix(digits, 16)
+        .ok()
+        .filter(|_| digits.len() == 6)
+        .ok_or_else(|| format!("invalid color `{hex}`, expected #rrggbb"))?;
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+fn parse_rgb(r: &str, g: &str, b: &str) -> Result<[u8; 3], String> {
+    let channel = |c: &str| {
+        c.parse::<u8>()
+            .map_err(|_| format!("invalid color channel `{c}`"))
+    };
+    Ok([channel(r)?, channel(g)?, channel(b)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `contents` to `name` in the temporary directory and loads it as a palette.
+    fn load_file(name: &str, contents: &str) -> Result<Palette, FractalError> {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, contents).unwrap();
+        let palette = Palette::load(&path);
+        fs::remove_file(path).unwrap();
+        palette
+    }
+
+    #[test]
+    fn rejects_empty_palettes() {
+        assert!(Palette::from_colors("empty", &[]).is_err());
+        assert!(Palette::from_stops("empty", &[]).is_err());
+        assert!(load_file("vulkano-fractals-empty.txt", "// nothing but comments\n").is_err());
+        assert!(load_file("vulkano-fractals-empty.json", r#"{"colors": []}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_stops_outside_the_period() {
+        for position in [-0.1, 1.0, f32::NAN, f32::INFINITY] {
+            let stops = [(0.0, [0, 0, 0]), (position, [255, 255, 255])];
+            assert!(Palette::from_stops("stops", &stops).is_err(), "{position}");
+        }
+        let text = "0.0 #000000\n1.5 #ffffff\n";
+        assert!(load_file("vulkano-fractals-stops.txt", text).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let mismatched = r##"{"colors": ["#000000", "#ffffff"], "positions": [0.0]}"##;
+        assert!(load_file("vulkano-fractals-mismatched.json", mismatched).is_err());
+        let some_positions = "0.0 #000000\n#ffffff\n";
+        assert!(load_file("vulkano-fractals-positions.txt", some_positions).is_err());
+        assert!(load_file("vulkano-fractals-hex.txt", "#12345\n").is_err());
+        assert!(load_file("vulkano-fractals-channel.txt", "0 128 256\n").is_err());
+    }
+
+    #[test]
+    fn loads_stops() {
+        let palette = load_file("vulkano-fractals-loads.txt", "0.5 0 0 0\n0 #ffffff\n").unwrap();
+        assert_eq!(palette.colors.len(), GRADIENT_RESOLUTION);
+        assert_eq!(palette.colors[0], [255, 255, 255, 255]);
+        assert_eq!(palette.colors[GRADIENT_RESOLUTION / 2], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn checks_width() {
+        let palette = Palette::built_in("fire").unwrap();
+        assert!(palette.check_width(GRADIENT_RESOLUTION as u32).is_ok());
+        assert!(matches!(
+            palette.check_width(GRADIENT_RESOLUTION as u32 - 1),
+            Err(FractalError::InvalidPalette(_))
+        ));
+        let empty = Palette {
+            colors: vec![],
+            ..palette
+        };
+        assert!(matches!(
+            empty.check_width(4096),
+            Err(FractalError::InvalidPalette(_))
+        ));
+    }
+
+    #[test]
+    fn next_built_in_wraps_around() {
+        let mut palette = Palette::built_in("rainbow").unwrap();
+        let names: Vec<_> = (0..BUILT_IN.len())
+            .map(|_| {
+                palette = palette.next_built_in();
+                palette.name.clone()
+            })
+            .collect();
+        assert_eq!(names, ["classic", "fire", "ocean", "grayscale", "rainbow"]);
+
+        let loaded = Palette::from_colors("gradient.txt", &[[0, 0, 0]]).unwrap();
+        assert_eq!(loaded.next_built_in().name, "rainbow");
+    }
+}
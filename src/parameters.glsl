@@ -0,0 +1,71 @@
+#include "precision.glsl"
+
+// Shared by the Julia and Mandelbrot shaders, `cs::Parameters` on the Rust side, or
+// `cs::ParametersF32` and `cs::ParametersDf64` in single and double-float precision.
+// `ParametersIn` is renamed along with them, the shader! macro needs one layout per name.
+// Doubles come first so neither std140 nor push constants need any padding between members.
+// The view maps a pixel at `x, y` (see `square_coordinates` in sampling.glsl) to `center + x * axis_x + y * axis_y`.
+#ifdef FLOAT32
+#define Parameters ParametersF32
+#define ParametersIn ParametersInF32
+#endif
+#ifdef DF64
+#define Parameters ParametersDf64
+#define ParametersIn ParametersInDf64
+#endif
+struct Parameters {
+    rvec2 center;
+    rvec2 axis_x;
+    rvec2 axis_y;
+    rvec2 mouse_pos;
+#ifdef DF64
+    // What's left of `center` and `mouse_pos` after rounding them to floats
+    vec2 center_lo;
+    vec2 mouse_pos_lo;
+#endif
+    real time;
+    real bailout;
+    int iterations;
+    int coloring;
+    int samples;
+    // One sample of the `samples` x `samples` grid to average into the image, or negative for
+    // the whole grid at once, see accumulate.glsl. It also keeps the size a multiple of 8, the
+    // alignment of `cs::Parameters`, because push constants must cover the whole Rust struct.
+    int sample_index;
+    float palette_offset;
+    float palette_speed;
+};
+
+layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+
+// Push constants, unless the entry shader asks for a uniform buffer because the device can't
+// push `Parameters` (see `ParameterBlock` in renderer.rs).
+#ifdef PARAMETERS_UNIFORM
+layout(std140, binding = 1) uniform ParametersIn {
+    Parameters p;
+};
+#else
+layout(push_constant) uniform ParametersIn {
+    Parameters p;
+};
+#endif
+
+// The point of the plane at `coordinates`, see `square_coordinates` in sampling.glsl
+rcomplex plane_point(rvec2 coordinates) {
+    const rvec2 offset = coordinates.x * p.axis_x + coordinates.y * p.axis_y;
+#ifdef DF64
+    return df64_complex_offset(p.center, p.center_lo, offset);
+#else
+    return p.center + offset;
+#endif
+}
+
+// The constant of the Julia set
+rcomplex julia_constant() {
+#ifdef DF64
+    return df64_complex_offset(p.mouse_pos, p.mouse_pos_lo, vec2(0.0));
+#else
+    return p.mouse_pos;
+#endif
+}
@@ -0,0 +1,119 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::bookmark::{Bookmark, PNG_KEYWORD};
+use crate::cs;
+use crate::error::FractalError;
+use crate::fractal::FractalKind;
+use crate::renderer::FractalRenderer;
+use crate::view::View;
+
+/// Largest tile rendered at once. Tiles are wide and short because a whole row of them is kept
+/// in memory until it has been written out: a 65536 pixel wide poster needs 64 MiB for it.
+pub const TILE_EXTENT: [u32; 2] = [4096, 256];
+
+/// Renders an image of `extent` pixels, which can be far larger than a single Vulkan image, and
+/// streams it into a PNG at `path`.
+///
+/// The view in `parameters` is split into tiles of at most [`TILE_EXTENT`] pixels that are
+/// rendered one after another, each with its own center and scale, so the result is the same as
+/// rendering the whole image at once. Only one row of tiles is ever held in memory.
+///
+/// The view is embedded as a [`Bookmark`], so [`Bookmark::load`] can open the PNG again.
+///
+/// The PNG is written next to `path` and only renamed to it once complete, so a render that
+/// fails doesn't leave a truncated image behind.
+pub fn render_poster(
+    renderer: &FractalRenderer,
+    kind: FractalKind,
+    parameters: &cs::Parameters,
+    extent: [u32; 2],
+    path: impl AsRef<Path>,
+) -> Result<(), FractalError> {
+    let path = path.as_ref();
+    let partial = partial_path(path);
+
+    let result = write_poster(renderer, kind, parameters, extent, &partial)
+        .and_then(|()| Ok(fs::rename(&partial, path)?));
+    if result.is_err() {
+        // It may not even have been created
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+/// `path` with `.part` appended, where the poster is written until it's done.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+fn write_poster(
+    renderer: &FractalRenderer,
+    kind: FractalKind,
+    parameters: &cs::Parameters,
+    extent: [u32; 2],
+    path: &Path,
+) -> Result<(), FractalError> {
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(writer, extent[0], extent[1]);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let view = View::from(parameters);
+    let bookmark = Bookmark::new(kind, &view, None, parameters, renderer.palette());
+    encoder.add_text_chunk("Software".to_string(), "vulkano-fractals".to_string())?;
+    encoder.add_itxt_chunk(PNG_KEYWORD.to_string(), bookmark.to_json())?;
+
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+
+    let size = [extent[0] as f64, extent[1] as f64];
+    let row_bytes = extent[0] as usize * 4;
+
+    for y in (0..extent[1]).step_by(TILE_EXTENT[1] as usize) {
+        let height = TILE_EXTENT[1].min(extent[1] - y);
+        let mut rows = vec![0; row_bytes * height as usize];
+
+        for x in (0..extent[0]).step_by(TILE_EXTENT[0] as usize) {
+            let width = TILE_EXTENT[0].min(extent[0] - x);
+
+            let mut tile_parameters = *parameters;
+            tile_view(&view, [x, y], [width, height], size).apply(&mut tile_parameters);
+            let pixels = renderer.render_to_rgba8(kind, &tile_parameters, [width, height])?;
+
+            /* Copy the tile into its place in the row */
+            let tile_row_bytes = width as usize * 4;
+            for (row, tile_row) in pixels.chunks_exact(tile_row_bytes).enumerate() {
+                let start = row * row_bytes + x as usize * 4;
+                rows[start..start + tile_row_bytes].copy_from_slice(tile_row);
+            }
+        }
+
+        stream.write_all(&rows)?;
+    }
+
+    stream.finish()?;
+    Ok(())
+}
+
+/// The part of `view` covered by the tile at `offset` with `extent` pixels, in an image of `size`
+/// pixels, as a view of its own.
+fn tile_view(view: &View, offset: [u32; 2], extent: [u32; 2], size: [f64; 2]) -> View {
+    let tile_center = [
+        offset[0] as f64 + extent[0] as f64 / 2.0,
+        offset[1] as f64 + extent[1] as f64 / 2.0,
+    ];
+
+    // `scale` is the span of the shorter side, so it shrinks with the tile's shorter side
+    let side = size[0].min(size[1]);
+    let tile_side = extent[0].min(extent[1]) as f64;
+
+    View {
+        center: view.pixel_to_plane(tile_center, size),
+        scale: view.scale * tile_side / side,
+        rotation: view.rotation,
+    }
+}
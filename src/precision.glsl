@@ -0,0 +1,41 @@
+// Precision of the fractal math, `Precision` in `precision.rs`. Shaders do their math with
+// `real` and `rvec2`, which are doubles unless the entry shader defines FLOAT32 or DF64 for
+// devices without shader_float64 (or with slow doubles).
+//
+// The iteration itself works on `rcomplex` through `complex_sqr_add`, which DF64 stores as
+// double-float pairs (see df64.glsl) while everything else, like coloring, uses plain floats.
+#if defined(FLOAT32) || defined(DF64)
+#define real float
+#define rvec2 vec2
+#else
+#define real double
+#define rvec2 dvec2
+#endif
+
+#ifdef DF64
+#include "df64.glsl"
+#define rcomplex vec4
+#else
+#define rcomplex rvec2
+#endif
+
+// z^2 + c
+rcomplex complex_sqr_add(rcomplex z, rcomplex c) {
+#ifdef DF64
+    return df64_complex_sqr_add(z, c);
+#else
+    return rvec2(
+        z.x * z.x - z.y * z.y + c.x, // real part
+        z.y * z.x + z.x * z.y + c.y // imaginary part
+    );
+#endif
+}
+
+// `z` rounded to `rvec2`, good enough for escape tests and coloring
+rvec2 complex_value(rcomplex z) {
+#ifdef DF64
+    return z.xz;
+#else
+    return z;
+#endif
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::str::FromStr;
+
+use vulkano::device::Device;
+
+use crate::cs;
+
+/// Floating point precision the Julia and Mandelbrot shaders iterate with.
+///
+/// Doubles need the optional `shader_float64` feature, which many integrated and mobile GPUs
+/// don't have, and run at a fraction of the float speed on most consumer GPUs. Double-float
+/// (`df64`) arithmetic keeps a pair of floats per value instead, for 48 bits of mantissa at a much
+/// higher throughput. Plain floats are fastest, but pixelate once the scale gets below about
+/// `1e-5`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Precision {
+    Float64,
+    Df64,
+    Float32,
+}
+
+impl Precision {
+    pub const ALL: [Precision; 3] = [Precision::Float64, Precision::Df64, Precision::Float32];
+
+    /// The precision to use on `device` unless asked for another one: doubles when the device was
+    /// created with them (see [`select_device`]), double-floats otherwise.
+    ///
+    /// [`select_device`]: crate::select_device
+    pub fn for_device(device: &Device) -> Precision {
+        if Precision::Float64.is_supported(device) {
+            Precision::Float64
+        } else {
+            Precision::Df64
+        }
+    }
+
+    /// Only doubles need a device feature, everything else works anywhere.
+    pub fn is_supported(self, device: &Device) -> bool {
+        match self {
+            Precision::Float64 => device.enabled_features().shader_float64,
+            Precision::Df64 | Precision::Float32 => true,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Precision::Float64 => "float64",
+            Precision::Df64 => "df64",
+            Precision::Float32 => "float32",
+        }
+    }
+
+    /// The precision after this one that `device` supports, wrapping around.
+    pub fn next_supported(self, device: &Device) -> Precision {
+        let index = Precision::ALL.iter().position(|&p| p == self).unwrap();
+        (1..=Precision::ALL.len())
+            .map(|offset| Precision::ALL[(index + offset) % Precision::ALL.len()])
+            .find(|p| p.is_supported(device))
+            .unwrap()
+    }
+}
+
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Precision::ALL
+            .into_iter()
+            .find(|precision| precision.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = Precision::ALL.iter().map(|p| p.name()).collect();
+                format!(
+                    "unknown precision `{s}`, expected one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/* Rounds everything to f32 for the single precision shaders */
+impl From<&cs::Parameters> for cs::ParametersF32 {
+    fn from(parameters: &cs::Parameters) -> Self {
+        let round = |v: [f64; 2]| [v[0] as f32, v[1] as f32];
+        cs::ParametersF32 {
+            center: round(parameters.center),
+            axis_x: round(parameters.axis_x),
+            axis_y: round(parameters.axis_y),
+            mouse_pos: round(parameters.mouse_pos),
+            time: parameters.time as f32,
+            bailout: parameters.bailout as f32,
+            iterations: parameters.iterations,
+            coloring: parameters.coloring,
+            samples: parameters.samples,
+            sample_index: parameters.sample_index,
+            palette_offset: parameters.palette_offset,
+            palette_speed: parameters.palette_speed,
+        }
+    }
+}
+
+/* Splits the center and Julia constant into double-float pairs, the rest only needs floats */
+impl From<&cs::Parameters> for cs::ParametersDf64 {
+    fn from(parameters: &cs::Parameters) -> Self {
+        let rounded = cs::ParametersF32::from(parameters);
+        let low = |v: [f64; 2], high: [f32; 2]| {
+            [
+                (v[0] - high[0] as f64) as f32,
+                (v[1] - high[1] as f64) as f32,
+            ]
+        };
+        cs::ParametersDf64 {
+            center: rounded.center,
+            axis_x: rounded.axis_x,
+            axis_y: rounded.axis_y,
+            mouse_pos: rounded.mouse_pos,
+            center_lo: low(parameters.center, rounded.center),
+            mouse_pos_lo: low(parameters.mouse_pos, rounded.mouse_pos),
+            time: rounded.time,
+            bailout: rounded.bailout,
+            iterations: rounded.iterations,
+            coloring: rounded.coloring,
+            samples: rounded.samples,
+            sample_index: rounded.sample_index,
+            palette_offset: rounded.palette_offset,
+            palette_speed: rounded.palette_speed,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use vulkano_fractals::{cs, DeepView, FractalKind, Palette, Precision};
+
+/// Each frame in flight has a fractal image per downscale, `Pass::level` indexes this.
+pub const DOWNSCALES: [u32; 3] = [1, 2, 4];
+
+/// The coarse passes right after a change, as `(level, iteration divisor)`.
+const COARSE_PASSES: [(usize, i32); 2] = [(2, 4), (1, 2)];
+
+/// Everything that decides what the window shows. Refinement starts over when any of it changes.
+#[derive(Clone, PartialEq)]
+pub struct Scene {
+    pub kind: FractalKind,
+    /// With `time` zeroed, it only matters while the palette cycles.
+    pub parameters: cs::Parameters,
+    pub deep_view: Option<DeepView>,
+    pub precision: Precision,
+    pub palette: Palette,
+    /// The Julia constant of the inset, while picking it.
+    pub inset: Option<[f64; 2]>,
+    pub extent: [u32; 2],
+}
+
+/// How to render one frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pass {
+    /// Index into `DOWNSCALES`, the image is rendered that many times smaller and blitted up.
+    pub level: usize,
+    iteration_divisor: i32,
+    /// The full resolution passes take the requested samples, the coarse ones only one.
+    full_samples: bool,
+    /// `Parameters::sample_index`, sample 0 and later must render into the same image.
+    pub sample_index: i32,
+}
+
+impl Pass {
+    /// Every sample at full resolution in one go, like a render without refinement.
+    const FULL: Pass = Pass {
+        level: 0,
+        iteration_divisor: 1,
+        full_samples: true,
+        sample_index: -1,
+    };
+
+    pub fn apply(&self, parameters: &mut cs::Parameters) {
+        parameters.iterations = (parameters.iterations / self.iteration_divisor).max(1);
+        if !self.full_samples {
+            parameters.samples = 1;
+        }
+        parameters.sample_index = self.sample_index;
+    }
+
+    /// Whether this pass adds to the image of the previous one rather than replacing it.
+    pub fn accumulates(&self) -> bool {
+        self.sample_index > 0
+    }
+}
+
+/// Progressive rendering of the window: a coarse pass as soon as the scene changes, sharper ones
+/// while it stays the same, then nothing until it changes again.
+///
+/// The passes are a quarter and a half of the resolution with as many fewer iterations, then the
+/// full image one supersample at a time (see accumulate.glsl). Deep zoom has no accumulation and
+/// takes all its samples at once.
+pub struct Refinement {
+    scene: Option<Scene>,
+    /// Passes rendered since the scene last changed.
+    passes: usize,
+}
+
+impl Refinement {
+    pub fn new() -> Self {
+        Refinement {
+            scene: None,
+            passes: 0,
+        }
+    }
+
+    /// Starts over with the coarse pass, e.g. when the window contents were lost.
+    pub fn restart(&mut self) {
+        self.scene = None;
+    }
+
+    /// The pass to render `scene` with next, or `None` when it is as sharp as it gets and the
+    /// window can keep showing the last frame.
+    ///
+    /// `continuous` is for animations and palette cycling, which change every frame anyway and
+    /// get [`Pass::FULL`] every time.
+    pub fn next_pass(&mut self, scene: &Scene, continuous: bool) -> Option<Pass> {
+        if continuous {
+            self.scene = None;
+            return Some(Pass::FULL);
+        }
+
+        if self.scene.as_ref() != Some(scene) {
+            self.scene = Some(scene.clone());
+            self.passes = 0;
+        }
+
+        let pass = match COARSE_PASSES.get(self.passes) {
+            Some(&(level, iteration_divisor)) => Pass {
+                level,
+                iteration_divisor,
+                full_samples: false,
+                sample_index: -1,
+            },
+            None => {
+                let index = self.passes - COARSE_PASSES.len();
+                let grid = scene.parameters.samples.max(1) as usize;
+
+                if scene.deep_view.is_some() {
+                    if index > 0 {
+                        return None;
+                    }
+                    Pass::FULL
+                } else {
+                    if index >= grid * grid {
+                        return None;
+                    }
+                    Pass {
+                        sample_index: index as i32,
+                        ..Pass::FULL
+                    }
+                }
+            }
+        };
+
+        self.passes += 1;
+        Some(pass)
+    }
+}
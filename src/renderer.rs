@@ -0,0 +1,866 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferToImageInfo, CopyImageToBufferInfo,
+    PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract,
+};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceExtensions, Queue};
+use vulkano::format::Format;
+use vulkano::image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
+use vulkano::pipeline::compute::ComputePipelineCreateInfo;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::{
+    ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo,
+};
+use vulkano::shader::{EntryPoint, ShaderModule};
+use vulkano::sync::{self, GpuFuture, HostAccessError, Sharing};
+use vulkano::ValidationError;
+
+use crate::cs;
+use crate::deep::DeepReference;
+use crate::device::{headless_instance, select_device, DeviceChoice};
+use crate::error::FractalError;
+use crate::fractal::FractalKind;
+use crate::palette::Palette;
+use crate::precision::Precision;
+
+/// Must match `local_size_x`/`local_size_y` in the compute shaders.
+pub const WORKGROUP_SIZE: u32 = 16;
+
+/// How the Julia and Mandelbrot shaders get their parameters.
+///
+/// Push constants go straight into the command buffer, so there is no buffer to write and keep
+/// alive per dispatch. Vulkan only guarantees 128 bytes of them though, so devices with less room
+/// than the largest parameter struct read a uniform buffer instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParameterBlock {
+    PushConstants,
+    UniformBuffer,
+}
+
+impl ParameterBlock {
+    /// Push constants if every precision's parameters fit in `device`'s push constant range.
+    pub fn for_device(device: &Device) -> ParameterBlock {
+        let largest = [
+            size_of::<cs::Parameters>(),
+            size_of::<cs::ParametersDf64>(),
+            size_of::<cs::ParametersF32>(),
+        ]
+        .into_iter()
+        .max()
+        .unwrap() as u32;
+
+        /* Vulkano wants the push constants to end strictly below the limit */
+        if largest
+            < device
+                .physical_device()
+                .properties()
+                .max_push_constants_size
+        {
+            ParameterBlock::PushConstants
+        } else {
+            ParameterBlock::UniformBuffer
+        }
+    }
+}
+
+/// Any error here means the shader doesn't fit the pipeline, so it's a
+/// [`FractalError::ShaderLoad`].
+pub fn create_compute_pipeline(
+    device: Arc<Device>,
+    shader: Arc<ShaderModule>,
+) -> Result<Arc<ComputePipeline>, FractalError> {
+    let entry_point: EntryPoint = shader
+        .entry_point("main")
+        .ok_or_else(|| FractalError::ShaderLoad("the shader has no `main` entry point".into()))?;
+
+    let stage = PipelineShaderStageCreateInfo::new(entry_point);
+
+    let layout = PipelineLayout::new(
+        device.clone(),
+        PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+            .into_pipeline_layout_create_info(device.clone())
+            .map_err(|e| FractalError::ShaderLoad(Box::new(e)))?,
+    )
+    .map_err(|e| FractalError::ShaderLoad(Box::new(e)))?;
+
+    ComputePipeline::new(
+        device.clone(),
+        None,
+        ComputePipelineCreateInfo::stage_layout(stage, layout),
+    )
+    .map_err(|e| FractalError::ShaderLoad(Box::new(e)))
+}
+
+/// Owns everything needed to run the fractal compute shader on a device.
+///
+/// The renderer can either be created on its own with [`FractalRenderer::new_headless`], or on top
+/// of a device and queue owned by the caller (e.g. one that can also present to a window) with
+/// [`FractalRenderer::new`].
+pub struct FractalRenderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    precision: Precision,
+    parameter_block: ParameterBlock,
+    compute_pipelines: HashMap<(FractalKind, Precision), Arc<ComputePipeline>>,
+    /// Perturbation needs doubles, so there is none in single precision.
+    deep_pipeline: Option<Arc<ComputePipeline>>,
+    palette: Palette,
+    /// Counts calls to `set_palette`, so render targets know when to rewrite their descriptor sets.
+    palette_generation: u64,
+    palette_view: Arc<ImageView>,
+    palette_sampler: Arc<Sampler>,
+    /// Queue families that use the images from `create_image`, the renderer's own first.
+    image_queue_families: Vec<u32>,
+}
+
+impl FractalRenderer {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>) -> Result<Self, FractalError> {
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+
+        let descriptor_set_allocator =
+            StandardDescriptorSetAllocator::new(device.clone(), Default::default());
+
+        let command_buffer_allocator =
+            StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+        /* Fall back to double-floats when the device can't do doubles */
+        let precision = Precision::for_device(&device);
+
+        let parameter_block = ParameterBlock::for_device(&device);
+
+        /* Every kind and precision gets its own pipeline up front, so switching them is free */
+        let compute_pipelines = FractalKind::ALL
+            .into_iter()
+            .flat_map(|kind| Precision::ALL.map(|precision| (kind, precision)))
+            .filter(|(_, precision)| precision.is_supported(&device))
+            .map(|(kind, precision)| {
+                let shader = kind
+                    .load_shader(device.clone(), precision, parameter_block)
+                    .map_err(|e| FractalError::ShaderLoad(Box::new(e)))?;
+                Ok((
+                    (kind, precision),
+                    create_compute_pipeline(device.clone(), shader)?,
+                ))
+            })
+            .collect::<Result<_, FractalError>>()?;
+
+        let deep_pipeline = if Precision::Float64.is_supported(&device) {
+            let deep_shader =
+                cs::load_deep(device.clone()).map_err(|e| FractalError::ShaderLoad(Box::new(e)))?;
+            Some(create_compute_pipeline(device.clone(), deep_shader)?)
+        } else {
+            None
+        };
+
+        /* Linear filtering between palette entries, repeating so the palette can cycle */
+        let palette_sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::Repeat; 3],
+                ..Default::default()
+            },
+        )?;
+
+        // The palette is replaced right away, it's just needed to construct the renderer
+        let palette = Palette {
+            name: "black".to_string(),
+            colors: vec![[0, 0, 0, 255]],
+        };
+        let palette_view = ImageView::new_default(upload_palette(
+            &memory_allocator,
+            &command_buffer_allocator,
+            &queue,
+            &palette,
+        )?)?;
+
+        let mut renderer = FractalRenderer {
+            device,
+            memory_allocator,
+            descriptor_set_allocator,
+            command_buffer_allocator,
+            precision,
+            parameter_block,
+            compute_pipelines,
+            deep_pipeline,
+            palette,
+            palette_generation: 0,
+            palette_view,
+            palette_sampler,
+            image_queue_families: vec![queue.queue_family_index()],
+            queue,
+        };
+
+        renderer.set_palette(&Palette::built_in("rainbow").unwrap())?;
+
+        Ok(renderer)
+    }
+
+    /// Creates its own instance and device, without any window or surface.
+    pub fn new_headless(device: DeviceChoice) -> Result<Self, FractalError> {
+        /* No display, so no surface extensions are needed */
+        let (device, queues) = select_device(
+            headless_instance()?,
+            DeviceExtensions::empty(),
+            None,
+            &device,
+        )?;
+
+        Self::new(device, queues.compute)
+    }
+
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &Arc<Queue> {
+        &self.queue
+    }
+
+    pub fn memory_allocator(&self) -> &Arc<StandardMemoryAllocator> {
+        &self.memory_allocator
+    }
+
+    pub fn command_buffer_allocator(&self) -> &StandardCommandBufferAllocator {
+        &self.command_buffer_allocator
+    }
+
+    /// What the fractal shaders iterate with, [`Precision::for_device`] unless changed with
+    /// [`FractalRenderer::set_precision`].
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    /// Uses `precision` for every dispatch recorded from now on, if the device supports it.
+    pub fn set_precision(&mut self, precision: Precision) -> Result<(), FractalError> {
+        if !precision.is_supported(&self.device) {
+            return Err(FractalError::MissingFeature {
+                feature: "shader_float64",
+                needed_for: format!("{precision} precision"),
+            });
+        }
+        self.precision = precision;
+        Ok(())
+    }
+
+    /// How the parameters reach the shaders, [`ParameterBlock::for_device`].
+    pub fn parameter_block(&self) -> ParameterBlock {
+        self.parameter_block
+    }
+
+    /// Whether [`FractalRenderer::record_deep_dispatch`] can be used, which needs doubles.
+    pub fn supports_deep_zoom(&self) -> bool {
+        self.deep_pipeline.is_some()
+    }
+
+    /// Uploads `palette` and uses it for every dispatch recorded from now on. Fails with
+    /// [`FractalError::InvalidPalette`] when it has no colors or more than the device's largest
+    /// 1D image.
+    pub fn set_palette(&mut self, palette: &Palette) -> Result<(), FractalError> {
+        let max_width = self
+            .device
+            .physical_device()
+            .properties()
+            .max_image_dimension1_d;
+        palette.check_width(max_width)?;
+
+        let image = upload_palette(
+            &self.memory_allocator,
+            &self.command_buffer_allocator,
+            &self.queue,
+            palette,
+        )?;
+        self.palette_view = ImageView::new_default(image)?;
+        self.palette = palette.clone();
+        self.palette_generation += 1;
+        Ok(())
+    }
+
+    /// Lets images made from now on also be used on `queue`, e.g. to blit them to a swapchain from
+    /// a present queue in another family than the renderer's compute queue (see [`Queues`]).
+    ///
+    /// Vulkano's command buffers don't record queue family ownership transfers, so the images are
+    /// shared concurrently between the families instead, and submissions on the two queues only
+    /// need a semaphore between them.
+    ///
+    /// [`Queues`]: crate::Queues
+    pub fn share_images_with(&mut self, queue: &Arc<Queue>) {
+        let family = queue.queue_family_index();
+        if !self.image_queue_families.contains(&family) {
+            self.image_queue_families.push(family);
+        }
+    }
+
+    /// The palette set with [`FractalRenderer::set_palette`], rainbow by default.
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Makes an image the compute shader can write to and that can be copied or blitted from.
+    pub fn create_image(&self, extent: [u32; 2]) -> Result<Arc<Image>, FractalError> {
+        Ok(Image::new(
+            self.memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: Format::R8G8B8A8_UNORM,
+                extent: [extent[0], extent[1], 1],
+                usage: ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+                sharing: if self.image_queue_families.len() > 1 {
+                    Sharing::Concurrent(self.image_queue_families.iter().copied().collect())
+                } else {
+                    Sharing::Exclusive
+                },
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+        )?)
+    }
+
+    /// Float image the shaders add samples up in. Only dispatches use it, so unlike the images of
+    /// [`FractalRenderer::create_image`] it isn't shared between queues.
+    fn create_accumulation_image(&self, extent: [u32; 2]) -> Result<Arc<Image>, FractalError> {
+        Ok(Image::new(
+            self.memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: Format::R32G32B32A32_SFLOAT,
+                extent: [extent[0], extent[1], 1],
+                usage: ImageUsage::STORAGE,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+        )?)
+    }
+
+    /// An image to render into again and again, see [`RenderTarget`].
+    pub fn create_target(&self, extent: [u32; 2]) -> Result<RenderTarget, FractalError> {
+        RenderTarget::new(self.create_image(extent)?)
+    }
+
+    /// Records the compute dispatch that fills `image` with the fractal described by `parameters`.
+    ///
+    /// This lets callers add their own commands (blits, copies) to the same command buffer. Every
+    /// call allocates a new descriptor set, and a parameter buffer without push constants, use
+    /// [`FractalRenderer::record_target_dispatch`] when rendering into the same image repeatedly.
+    pub fn record_dispatch<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        image: Arc<Image>,
+        kind: FractalKind,
+        parameters: &cs::Parameters,
+    ) -> Result<(), FractalError> {
+        let mut target = RenderTarget::new(image)?;
+        self.record_target_dispatch(builder, &mut target, kind, parameters)
+    }
+
+    /// Like [`FractalRenderer::record_dispatch`], but reuses the parameter buffer and descriptor
+    /// set of a previous dispatch into `target` once the GPU is done with it. Descriptor sets are
+    /// only rewritten when the precision or palette changed.
+    pub fn record_target_dispatch<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        target: &mut RenderTarget,
+        kind: FractalKind,
+        parameters: &cs::Parameters,
+    ) -> Result<(), FractalError> {
+        let compute_pipeline = &self.compute_pipelines[&(kind, self.precision)];
+
+        let accumulates = parameters.sample_index >= 0;
+        let slot = self.target_slot(target, compute_pipeline, accumulates)?;
+        if let Some(parameters_buffer) = &slot.parameters_buffer {
+            write_parameters(parameters_buffer, slot.precision, parameters)
+                .map_err(|e| FractalError::Allocation(Box::new(e)))?;
+        }
+        let set = slot.descriptor_set.clone();
+
+        let [width, height, _] = target.image.extent();
+
+        // Round up so the whole image is covered, the shader skips invocations outside of it.
+        builder
+            .bind_pipeline_compute(compute_pipeline.clone())?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                compute_pipeline.layout().clone(),
+                0,
+                set,
+            )?;
+
+        if self.parameter_block == ParameterBlock::PushConstants {
+            push_parameters(
+                builder,
+                compute_pipeline.layout().clone(),
+                self.precision,
+                parameters,
+            )?;
+        }
+
+        builder.dispatch([
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1,
+        ])?;
+
+        Ok(())
+    }
+
+    /// A slot of `target` whose parameter buffer the GPU isn't reading anymore, up to date with
+    /// the current precision and palette. Adds a slot when all of them are still in flight, with
+    /// push constants there is no buffer and the first slot is always free.
+    ///
+    /// The shaders always bind the sums of accumulated samples, but only when `accumulates` do
+    /// they need to be as large as the image.
+    fn target_slot<'a>(
+        &self,
+        target: &'a mut RenderTarget,
+        compute_pipeline: &Arc<ComputePipeline>,
+        accumulates: bool,
+    ) -> Result<&'a mut TargetSlot, FractalError> {
+        let [width, height, _] = target.image.extent();
+        let accumulation_extent = if accumulates { [width, height] } else { [1, 1] };
+        let accumulation = match &target.accumulation {
+            Some(view) if !accumulates || view.image().extent() == target.image.extent() => {
+                view.clone()
+            }
+            _ => {
+                /* Submissions still using the old sums keep them alive, the slots are rebuilt */
+                let view =
+                    ImageView::new_default(self.create_accumulation_image(accumulation_extent)?)?;
+                target.accumulation = Some(view.clone());
+                target.slots.clear();
+                target.next_slot = 0;
+                view
+            }
+        };
+
+        /* Start after the last slot used, which is the most likely to still be in flight */
+        let count = target.slots.len();
+        let free = (0..count)
+            .map(|offset| (target.next_slot + offset) % count)
+            .find(|&i| match &target.slots[i].parameters_buffer {
+                Some(buffer) => buffer.write().is_ok(),
+                None => true,
+            });
+
+        let index = match free {
+            Some(index) => index,
+            None => {
+                target.slots.push(self.create_target_slot(
+                    &target.view,
+                    &accumulation,
+                    compute_pipeline,
+                )?);
+                count
+            }
+        };
+        target.next_slot = (index + 1) % target.slots.len();
+
+        let slot = &mut target.slots[index];
+        if slot.precision != self.precision || slot.palette_generation != self.palette_generation {
+            *slot = self.create_target_slot(&target.view, &accumulation, compute_pipeline)?;
+        }
+        Ok(slot)
+    }
+
+    fn create_target_slot(
+        &self,
+        view: &Arc<ImageView>,
+        accumulation: &Arc<ImageView>,
+        compute_pipeline: &Arc<ComputePipeline>,
+    ) -> Result<TargetSlot, FractalError> {
+        /* Every kind and precision has the same bindings, so any of their pipelines will do */
+        let layout = compute_pipeline.layout().set_layouts().first().unwrap();
+
+        let mut descriptor_writes = vec![
+            WriteDescriptorSet::image_view(0, view.clone()),
+            WriteDescriptorSet::image_view_sampler(
+                2,
+                self.palette_view.clone(),
+                self.palette_sampler.clone(),
+            ),
+            WriteDescriptorSet::image_view(3, accumulation.clone()),
+        ];
+
+        let parameters_buffer = match self.parameter_block {
+            ParameterBlock::PushConstants => None,
+            ParameterBlock::UniformBuffer => {
+                let buffer = self.create_parameters_buffer()?;
+                descriptor_writes.push(WriteDescriptorSet::buffer(1, buffer.clone()));
+                Some(buffer)
+            }
+        };
+
+        let descriptor_set: Arc<PersistentDescriptorSet> = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            layout.clone(),
+            descriptor_writes,
+            [],
+        )?;
+
+        Ok(TargetSlot {
+            precision: self.precision,
+            palette_generation: self.palette_generation,
+            parameters_buffer,
+            descriptor_set,
+        })
+    }
+
+    /// A uniform buffer for the parameter struct of the current precision, as bytes.
+    fn create_parameters_buffer(&self) -> Result<Subbuffer<[u8]>, FractalError> {
+        let buffer_info = BufferCreateInfo {
+            usage: BufferUsage::UNIFORM_BUFFER,
+            ..Default::default()
+        };
+        let allocation_info = AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        };
+
+        /* The other precisions take the same parameters rounded to f32, or split into pairs */
+        let parameters_buffer = match self.precision {
+            Precision::Float64 => Buffer::new_sized::<cs::Parameters>(
+                self.memory_allocator.clone(),
+                buffer_info,
+                allocation_info,
+            )
+            .map(|buffer| buffer.into_bytes()),
+            Precision::Df64 => Buffer::new_sized::<cs::ParametersDf64>(
+                self.memory_allocator.clone(),
+                buffer_info,
+                allocation_info,
+            )
+            .map(|buffer| buffer.into_bytes()),
+            Precision::Float32 => Buffer::new_sized::<cs::ParametersF32>(
+                self.memory_allocator.clone(),
+                buffer_info,
+                allocation_info,
+            )
+            .map(|buffer| buffer.into_bytes()),
+        }?;
+
+        Ok(parameters_buffer)
+    }
+
+    /// Records the perturbation dispatch that fills `image` with a deep zoom.
+    ///
+    /// The reference orbit and series are computed on the CPU, see [`DeepReference::new`].
+    /// Fails with [`FractalError::MissingFeature`] unless [`FractalRenderer::supports_deep_zoom`].
+    pub fn record_deep_dispatch<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        image: Arc<Image>,
+        reference: &DeepReference,
+    ) -> Result<(), FractalError> {
+        let deep_pipeline =
+            self.deep_pipeline
+                .as_ref()
+                .ok_or_else(|| FractalError::MissingFeature {
+                    feature: "shader_float64",
+                    needed_for: "deep zoom".to_string(),
+                })?;
+
+        let parameters_buffer = Buffer::from_data(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            reference.parameters,
+        )?;
+
+        let orbit_buffer = Buffer::from_iter(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            reference.orbit.iter().copied(),
+        )?;
+
+        let [width, height, _] = image.extent();
+
+        let view = ImageView::new_default(image)?;
+
+        let layout = deep_pipeline.layout().set_layouts().first().unwrap();
+
+        let set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            layout.clone(),
+            [
+                WriteDescriptorSet::image_view(0, view),
+                WriteDescriptorSet::buffer(1, parameters_buffer),
+                WriteDescriptorSet::buffer(2, orbit_buffer),
+                WriteDescriptorSet::image_view_sampler(
+                    3,
+                    self.palette_view.clone(),
+                    self.palette_sampler.clone(),
+                ),
+            ],
+            [],
+        )?;
+
+        builder
+            .bind_pipeline_compute(deep_pipeline.clone())?
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                deep_pipeline.layout().clone(),
+                0,
+                set,
+            )?
+            .dispatch([
+                width.div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(WORKGROUP_SIZE),
+                1,
+            ])?;
+
+        Ok(())
+    }
+
+    /// Renders the fractal into a new image and waits for the GPU to finish.
+    pub fn render(
+        &self,
+        kind: FractalKind,
+        parameters: &cs::Parameters,
+        extent: [u32; 2],
+    ) -> Result<Arc<Image>, FractalError> {
+        let image = self.create_image(extent)?;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+
+        self.record_dispatch(&mut builder, image.clone(), kind, parameters)?;
+
+        self.submit_and_wait(builder)?;
+
+        Ok(image)
+    }
+
+    /// Renders the fractal and reads it back as tightly packed RGBA8 rows.
+    pub fn render_to_rgba8(
+        &self,
+        kind: FractalKind,
+        parameters: &cs::Parameters,
+        extent: [u32; 2],
+    ) -> Result<Vec<u8>, FractalError> {
+        let image = self.create_image(extent)?;
+
+        /* Host visible buffer to copy the finished image into */
+        let output_buffer = Buffer::new_slice::<u8>(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            extent[0] as u64 * extent[1] as u64 * 4,
+        )?;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )?;
+
+        self.record_dispatch(&mut builder, image.clone(), kind, parameters)?;
+
+        builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+            image,
+            output_buffer.clone(),
+        ))?;
+
+        self.submit_and_wait(builder)?;
+
+        let buffer_content = output_buffer
+            .read()
+            .map_err(|e| FractalError::Allocation(Box::new(e)))?;
+        Ok(buffer_content.to_vec())
+    }
+
+    fn submit_and_wait(
+        &self,
+        builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), FractalError> {
+        let command_buffer = builder.build()?;
+
+        /* Nothing to present, so just wait for the GPU to finish */
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+
+        Ok(())
+    }
+}
+
+/// An image that is rendered into over and over, like every frame of a window, together with
+/// the parameter buffers and descriptor sets for doing so without allocating anything per frame.
+///
+/// Each dispatch uses a slot whose parameter buffer the GPU is done with, so there are as many
+/// slots as there are dispatches in flight. A slot only counts as in flight once its command
+/// buffer is submitted, so record at most one dispatch per target into a command buffer.
+pub struct RenderTarget {
+    image: Arc<Image>,
+    view: Arc<ImageView>,
+    /// Sums of the samples accumulated so far, see accumulate.glsl. Made on the first dispatch,
+    /// 1x1 until one accumulates.
+    accumulation: Option<Arc<ImageView>>,
+    slots: Vec<TargetSlot>,
+    next_slot: usize,
+}
+
+struct TargetSlot {
+    precision: Precision,
+    palette_generation: u64,
+    /// One of the parameter structs for `precision`, as bytes. `None` with push constants.
+    parameters_buffer: Option<Subbuffer<[u8]>>,
+    descriptor_set: Arc<PersistentDescriptorSet>,
+}
+
+impl RenderTarget {
+    /// Renders into `image`, which needs [`ImageUsage::STORAGE`] like the images made by
+    /// [`FractalRenderer::create_image`].
+    pub fn new(image: Arc<Image>) -> Result<Self, FractalError> {
+        let view = ImageView::new_default(image.clone())?;
+        Ok(RenderTarget {
+            image,
+            view,
+            accumulation: None,
+            slots: Vec::new(),
+            next_slot: 0,
+        })
+    }
+
+    pub fn image(&self) -> &Arc<Image> {
+        &self.image
+    }
+}
+
+/// Fails if the GPU is still reading `buffer`.
+fn write_parameters(
+    buffer: &Subbuffer<[u8]>,
+    precision: Precision,
+    parameters: &cs::Parameters,
+) -> Result<(), HostAccessError> {
+    match precision {
+        Precision::Float64 => *buffer.reinterpret_ref::<cs::Parameters>().write()? = *parameters,
+        Precision::Df64 => {
+            *buffer.reinterpret_ref::<cs::ParametersDf64>().write()? = parameters.into()
+        }
+        Precision::Float32 => {
+            *buffer.reinterpret_ref::<cs::ParametersF32>().write()? = parameters.into()
+        }
+    }
+    Ok(())
+}
+
+/// Records the parameter struct for `precision` as push constants.
+fn push_parameters<L>(
+    builder: &mut AutoCommandBufferBuilder<L>,
+    layout: Arc<PipelineLayout>,
+    precision: Precision,
+    parameters: &cs::Parameters,
+) -> Result<(), Box<ValidationError>> {
+    match precision {
+        Precision::Float64 => builder.push_constants(layout, 0, *parameters)?,
+        Precision::Df64 => {
+            builder.push_constants(layout, 0, cs::ParametersDf64::from(parameters))?
+        }
+        Precision::Float32 => {
+            builder.push_constants(layout, 0, cs::ParametersF32::from(parameters))?
+        }
+    };
+    Ok(())
+}
+
+/// Copies the palette colors into a new 1D image and waits for the upload to finish.
+fn upload_palette(
+    memory_allocator: &Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: &StandardCommandBufferAllocator,
+    queue: &Arc<Queue>,
+    palette: &Palette,
+) -> Result<Arc<Image>, FractalError> {
+    let image = Image::new(
+        memory_allocator.clone(),
+        ImageCreateInfo {
+            image_type: ImageType::Dim1d,
+            format: Format::R8G8B8A8_UNORM,
+            extent: [palette.colors.len() as u32, 1, 1],
+            usage: ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+            ..Default::default()
+        },
+    )?;
+
+    let staging_buffer = Buffer::from_iter(
+        memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        palette.colors.iter().copied(),
+    )?;
+
+    let mut builder = AutoCommandBufferBuilder::primary(
+        command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )?;
+
+    builder.copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+        staging_buffer,
+        image.clone(),
+    ))?;
+
+    builder
+        .build()?
+        .execute(queue.clone())?
+        .then_signal_fence_and_flush()?
+        .wait(None)?;
+
+    Ok(image)
+}
@@ -0,0 +1,27 @@
+// Supersampling shared by all fractal shaders. Each shader defines `sample_color`, the color of
+// the single point at `coordinates`, and its `main` writes `supersample(n)` to the image, or
+// `pixel_color()` from accumulate.glsl.
+vec4 sample_color(rvec2 coordinates);
+
+// A point inside this invocation's pixel (`subpixel` in [0, 1)) relative to the center of the
+// image, in units of its shorter side, so pixels stay square at any aspect ratio.
+// Matches `view::square_coordinates`.
+rvec2 square_coordinates(rvec2 subpixel) {
+    const rvec2 size = rvec2(imageSize(img));
+    return (gl_GlobalInvocationID.xy + subpixel - size * 0.5) / min(size.x, size.y);
+}
+
+// Averages an n x n grid of samples spread evenly over the pixel, n = 1 samples its center.
+vec4 supersample(int n) {
+    n = max(n, 1);
+
+    vec4 color = vec4(0.0);
+    for (int y = 0; y < n; y += 1) {
+        for (int x = 0; x < n; x += 1) {
+            const rvec2 subpixel = (rvec2(x, y) + rvec2(0.5)) / real(n);
+            color += sample_color(square_coordinates(subpixel));
+        }
+    }
+
+    return color / float(n * n);
+}
@@ -0,0 +1,38 @@
+use std::time::{Duration, Instant};
+
+/// Averages the frame rate and how long the CPU spends preparing each frame, over about a second.
+pub struct FrameTimer {
+    frames: u32,
+    cpu_time: Duration,
+    since: Instant,
+}
+
+impl FrameTimer {
+    pub fn start() -> Self {
+        FrameTimer {
+            frames: 0,
+            cpu_time: Duration::ZERO,
+            since: Instant::now(),
+        }
+    }
+
+    /// Counts a frame that kept the CPU busy for `cpu_time`. Returns the averages once a second
+    /// has passed, and starts over.
+    pub fn frame(&mut self, cpu_time: Duration) -> Option<String> {
+        self.frames += 1;
+        self.cpu_time += cpu_time;
+
+        let elapsed = self.since.elapsed();
+        if elapsed < Duration::from_secs(1) {
+            return None;
+        }
+
+        let report = format!(
+            "{:.1} fps, {:.3} ms of CPU time per frame",
+            self.frames as f64 / elapsed.as_secs_f64(),
+            self.cpu_time.as_secs_f64() * 1000.0 / self.frames as f64
+        );
+        *self = FrameTimer::start();
+        Some(report)
+    }
+}
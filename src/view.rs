@@ -0,0 +1,82 @@
+use crate::cs;
+
+/// The region of the complex plane that ends up on screen.
+///
+/// Uses the same mapping as the shaders: a pixel at `position` in an image of `size` pixels is
+/// `(position - size / 2) / min(size)` away from the center, in units of the shorter side, and
+/// sits at `center + x * axis_x + y * axis_y` (see [`View::axes`]). So `scale` is the span of the
+/// shorter side, and pixels are square whatever the aspect ratio.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    pub center: [f64; 2],
+    pub scale: f64,
+    /// Counterclockwise, in radians.
+    pub rotation: f64,
+}
+
+impl View {
+    /// Where one unit (the shorter side) along the image's x and y axes ends up in the plane.
+    pub fn axes(&self) -> [[f64; 2]; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        [
+            [self.scale * cos, self.scale * sin],
+            [-self.scale * sin, self.scale * cos],
+        ]
+    }
+
+    /// The point of the complex plane under `position`, in pixels of an image of `size` pixels.
+    pub fn pixel_to_plane(&self, position: [f64; 2], size: [f64; 2]) -> [f64; 2] {
+        let [x, y] = square_coordinates(position, size);
+        let [axis_x, axis_y] = self.axes();
+        [
+            self.center[0] + x * axis_x[0] + y * axis_y[0],
+            self.center[1] + x * axis_x[1] + y * axis_y[1],
+        ]
+    }
+
+    /// Moves the view so that the plane follows a drag of `delta` pixels.
+    pub fn pan_pixels(&mut self, delta: [f64; 2], size: [f64; 2]) {
+        let origin = self.pixel_to_plane([0.0, 0.0], size);
+        let moved = self.pixel_to_plane(delta, size);
+        self.center[0] -= moved[0] - origin[0];
+        self.center[1] -= moved[1] - origin[1];
+    }
+
+    /// Multiplies the scale by `factor` (< 1 zooms in), keeping the point under `position` fixed.
+    pub fn zoom_at(&mut self, factor: f64, position: [f64; 2], size: [f64; 2]) {
+        let anchor = self.pixel_to_plane(position, size);
+        self.scale *= factor;
+        let moved = self.pixel_to_plane(position, size);
+        self.center[0] += anchor[0] - moved[0];
+        self.center[1] += anchor[1] - moved[1];
+    }
+
+    /// Writes `center` and the axes into the shader parameters, leaving the rest untouched.
+    pub fn apply(&self, parameters: &mut cs::Parameters) {
+        let [axis_x, axis_y] = self.axes();
+        parameters.center = self.center;
+        parameters.axis_x = axis_x;
+        parameters.axis_y = axis_y;
+    }
+}
+
+impl From<&cs::Parameters> for View {
+    fn from(parameters: &cs::Parameters) -> Self {
+        let [x, y] = parameters.axis_x;
+        View {
+            center: parameters.center,
+            scale: x.hypot(y),
+            rotation: y.atan2(x),
+        }
+    }
+}
+
+/// `position` relative to the center of the image, in units of its shorter side. Matches
+/// `square_coordinates` in sampling.glsl.
+pub fn square_coordinates(position: [f64; 2], size: [f64; 2]) -> [f64; 2] {
+    let side = size[0].min(size[1]);
+    [
+        (position[0] - size[0] / 2.0) / side,
+        (position[1] - size[1] / 2.0) / side,
+    ]
+}